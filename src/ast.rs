@@ -1,13 +1,16 @@
+use phf::phf_map;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use regex::Regex;
-use crate::ast_node::{ASTNode, Namespace, SourceFile, VarNode};
-use crate::data_type::{compile_native_types, Type};
+use crate::ast_node::{adopt_literal_type, block_always_returns, Block, ForLoop, IfStatement, LoopControl, LoopControlKind, LoopSource, MatchStatement, Mutability, Namespace, Range, ReturnStatement, TypeDefinition, VarDefinition, VarNode, WhileLoop};
+use crate::data_type::{compile_native_types, parse_type, Type, VariantDeclaration, BOOL_TYPE, VOID_TYPE};
 use crate::line::{Line, LineIterator};
 use crate::compiler_error::{raise_compiler_error, CompilerError};
-use crate::compiler_error::CompilerError::UndefinedType;
-use crate::function_node::Function;
+use crate::compiler_error::CompilerError::{AssignmentTypeError, ConditionTypeError, IndentError, InvalidExpression, InvalidStep, LabelWithoutLoop, LoopControlOutsideLoop, MisplacedBranch, MissingReturn, NonConstantExpression, NonExhaustiveMatch, NotIterable, NotMatchable, RangeTypeError, ReturnOutsideFunction, ReturnTypeError, SymbolAlreadyDefined, UndefinedLabel, UnexpectedToken, UnreachablePattern, VoidValue};
+use crate::function_node::{assert_has_min_tokens, assert_unique_var, assert_valid_symbol, is_valid_symbol, Function};
+use crate::conversion::{coerce, common_type, convert};
+use crate::expression::{expression_node, typed_expression_node, ParseResult};
+use crate::pattern::{is_matchable, parse_pattern, Coverage};
 use crate::tokenizer::tokenize_file;
 
 const ASSIGNMENT_TOKEN: &str = "=";
@@ -15,98 +18,871 @@ const PAREN_OPEN_TOKEN: &str = "(";
 const PAREN_CLOSE_TOKEN: &str = ")";
 const PARAM_DELIMITER: &str = ",";
 
-// fn var_def_node(data_type: Rc<Type>, line: &Line, namespace: Rc<RefCell<Namespace>>) -> VarNode {
-//     const VAR_NAME_INDEX: usize = 1;
-//
-//     let var_name = line[1].clone();
-//     assert_unique_var(var_name.clone(), line, namespace.clone());
-//
-//     let var_node = VarNode::new(data_type, var_name);
-//     namespace.borrow_mut().add_var(var_node.clone());
-//     var_node
-// }
-
-// fn symbol_definition(data_type: Rc<Type>,
-//                          curr_line: &Line,
-//                          types: &HashMap<String, Rc<Type>>,
-//                          namespace: &mut Rc<RefCell<Namespace>>
-// ) -> Result<Box<dyn ASTNode>, CompilerError> {
-//     let symbol = curr_line[1].clone();
-//     assert_valid_symbol(symbol, curr_line);
-//
-//     let token = &curr_line[2];
-//     match token.as_str() {
-//         ASSIGNMENT_TOKEN => {
-//             Ok(Box::new(var_def_node(data_type, curr_line, namespace.clone())))
-//         }
-//         PAREN_OPEN_TOKEN => {
-//             let mut func_node = function_def_node(data_type, curr_line, types);
-//             func_node.namespace.borrow_mut().parent = Some(namespace.clone());
-//             *namespace = func_node.namespace.clone();
-//             Ok(Box::new(func_node))
-//         }
-//         _ => {
-//             Err(CompilerError::InvalidDefinition(curr_line.line_num))
-//         }
-//     }
-// }
-
-fn create_ast_node(curr_line: &Line,
-                       types: &HashMap<String, Rc<Type>>,
-                       namespace: &mut Rc<RefCell<Namespace>>
-) -> Result<Box<dyn ASTNode>, CompilerError> {
-    let start_token = curr_line.get_token(0);
-
-
-
-    Err(CompilerError::InvalidDefinition(curr_line.line_num))
-}
-
-fn update_namespace(line: &Line, prev_indent: usize, namespace: &mut Rc<RefCell<Namespace>>) {
-    if line.indent > prev_indent + 1 {
-        raise_compiler_error(CompilerError::IndentError(line.line_num));
-    }
-
-    for _ in line.indent..prev_indent {
-        let parent_ns = namespace.borrow().parent.as_ref().unwrap().clone();
-        *namespace = parent_ns;
-    }
-}
-
-fn add_function_def_nodes(source_file: &mut SourceFile,
-                          lines: &Vec<Line>,
-                          types: &HashMap<String, Rc<Type>>
-) {
-    for line in lines {
-        match Function::from(line, types) {
-            Some(func_node) => {
-                source_file.add_function(func_node);
+const BLOCK_START_TOKEN: &str = ":";
+const LET_TOKEN: &str = "let";
+const CONST_TOKEN: &str = "const";
+const FINAL_TOKEN: &str = "final";
+const IF_TOKEN: &str = "if";
+const ELIF_TOKEN: &str = "elif";
+const ELSE_TOKEN: &str = "else";
+const WHILE_TOKEN: &str = "while";
+const BREAK_TOKEN: &str = "break";
+const FOR_TOKEN: &str = "for";
+const IN_TOKEN: &str = "in";
+const STEP_TOKEN: &str = "step";
+const RANGE_TOKEN: &str = "..";
+const INCLUSIVE_RANGE_TOKEN: &str = "..=";
+const STRUCT_TOKEN: &str = "struct";
+const ENUM_TOKEN: &str = "enum";
+
+type StatementParser = fn(&mut Parser, &Line, &Rc<RefCell<Namespace>>) -> ParseResult;
+
+static KEYWORD_PARSERS: phf::Map<&'static str, StatementParser> = phf_map! {
+    "if" => if_statement_node,
+    "elif" => misplaced_branch,
+    "else" => misplaced_branch,
+    "while" => while_loop_node,
+    "for" => for_loop_node,
+    "break" => loop_control_node,
+    "continue" => loop_control_node,
+    "match" => match_node,
+    "return" => return_node,
+    "struct" => type_definition_node,
+    "enum" => type_definition_node,
+};
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    line_index: usize,
+    types: HashMap<String, Rc<Type>>,
+    loop_labels: Vec<Option<Rc<String>>>,
+    return_types: Vec<Rc<Type>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(lines: Vec<Line<'a>>, types: HashMap<String, Rc<Type>>) -> Self {
+        Self { lines, line_index: 0, types, loop_labels: Vec::new(), return_types: Vec::new() }
+    }
+
+    fn peek_line(&self) -> Option<Line<'a>> {
+        self.lines.get(self.line_index).copied()
+    }
+
+    fn void_type(&self) -> Rc<Type> {
+        self.types[VOID_TYPE].clone()
+    }
+
+    fn parse_block(&mut self, indent: usize, namespace: &Rc<RefCell<Namespace>>) -> Result<Block, CompilerError> {
+        let mut block = Vec::new();
+
+        while let Some(line) = self.peek_line() {
+            if line.indent < indent {
+                break;
             }
-            None => {}
+            if line.indent > indent {
+                return Err(IndentError(line.line_num));
+            }
+
+            self.line_index += 1;
+            block.push(self.create_ast_node(&line, namespace)?);
+        }
+
+        Ok(block)
+    }
+
+    fn parse_child_block(&mut self, header: &Line, namespace: &Rc<RefCell<Namespace>>) -> Result<Block, CompilerError> {
+        let child_namespace = Rc::new(RefCell::new(Namespace::with_parent(namespace.clone())));
+        self.parse_indented_block(header, &child_namespace)
+    }
+
+    fn parse_indented_block(&mut self, header: &Line, namespace: &Rc<RefCell<Namespace>>) -> Result<Block, CompilerError> {
+        let indent = header.indent + 1;
+        match self.peek_line() {
+            Some(line) if line.indent == indent => {}
+            Some(line) => return Err(IndentError(line.line_num)),
+            None => return Err(IndentError(header.line_num + 1)),
+        }
+
+        self.parse_block(indent, namespace)
+    }
+
+    fn condition_node(&self, line: &Line, start: usize, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+        let condition = expression_node(line, start, header_end(line), &self.types, namespace.clone())?;
+        if condition.get_type().name() != BOOL_TYPE {
+            return Err(ConditionTypeError(line.line_num, condition.get_type().to_string()));
         }
+
+        Ok(condition)
+    }
+
+    fn create_ast_node(&mut self, curr_line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+        let start_token = curr_line.get_token(0);
+
+        if let Some(statement_parser) = KEYWORD_PARSERS.get(start_token.as_str()) {
+            return statement_parser(self, curr_line, namespace);
+        }
+        if curr_line.len() > 2 && curr_line[1].as_str() == BLOCK_START_TOKEN {
+            return labeled_statement_node(self, curr_line, namespace);
+        }
+
+        let types = &self.types;
+        if let Some(mutability) = binding_mutability(start_token) {
+            const MIN_BINDING_TOKENS: usize = 2;
+            assert_has_min_tokens(MIN_BINDING_TOKENS, curr_line);
+
+            return match parse_type(curr_line.tokens, 1, types) {
+                Some((data_type, name_index)) => symbol_definition(Some(data_type), mutability, name_index, curr_line, types, namespace),
+                None => symbol_definition(None, mutability, 1, curr_line, types, namespace),
+            };
+        }
+        if start_token == LET_TOKEN {
+            return symbol_definition(None, Mutability::Mutable, 1, curr_line, types, namespace);
+        }
+        if is_function_def(curr_line, types) {
+            return function_def_node(self, curr_line, namespace);
+        }
+        // A type followed by anything but a name starts an expression, such as `Shape.Empty`
+        let declared_type = parse_type(curr_line.tokens, 0, types)
+            .filter(|&(_, name_index)| curr_line.tokens.get(name_index).is_some_and(|token| is_valid_symbol(token)));
+        if let Some((data_type, name_index)) = declared_type {
+            return symbol_definition(Some(data_type), Mutability::Mutable, name_index, curr_line, types, namespace);
+        }
+
+        expression_node(curr_line, 0, curr_line.len(), types, namespace.clone())
     }
 }
 
-pub fn generate_ast(file_name: &String) {
-    let tokens = tokenize_file(file_name).unwrap();
-    let mut types = compile_native_types();
+fn if_statement_node(parser: &mut Parser, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const CONDITION_START_INDEX: usize = 1;
 
-    let mut namespace = Rc::new(RefCell::new(Namespace::new()));
-    let lines: Vec<Line> = LineIterator::new(file_name, &tokens).collect();
-    let mut prev_indent = 0;
+    let condition = parser.condition_node(line, CONDITION_START_INDEX, namespace)?;
+    let body = parser.parse_child_block(line, namespace)?;
+    let mut if_node = IfStatement::new(parser.void_type(), condition, body);
 
-    for curr_line in lines {
-        if curr_line.start < curr_line.end {
-            update_namespace(&curr_line, prev_indent, &mut namespace);
-            prev_indent = curr_line.indent;
+    while let Some(next_line) = parser.peek_line() {
+        if next_line.indent != line.indent {
+            break;
+        }
 
-            match create_ast_node(&curr_line, &mut types, &mut namespace) {
-                Ok(node) => {
-                    println!("{:?}", node.get_type());
+        match next_line.get_token(0).as_str() {
+            ELIF_TOKEN => {
+                parser.line_index += 1;
+                let condition = parser.condition_node(&next_line, CONDITION_START_INDEX, namespace)?;
+                let body = parser.parse_child_block(&next_line, namespace)?;
+                if_node.add_elif(condition, body);
+            }
+            ELSE_TOKEN => {
+                parser.line_index += 1;
+                if header_end(&next_line) != 1 {
+                    return Err(InvalidExpression(next_line.line_num));
                 }
-                Err(e) => raise_compiler_error(e),
+                if_node.set_else(parser.parse_child_block(&next_line, namespace)?);
+                break;
             }
-            println!("{:?}", curr_line.tokens);
+            _ => break,
+        }
+    }
+
+    Ok(Box::new(if_node))
+}
+
+// `match value:` followed by arms of `pattern:` or `pattern if guard:`, each with an indented body
+fn match_node(parser: &mut Parser, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const VALUE_START_INDEX: usize = 1;
+
+    let value = expression_node(line, VALUE_START_INDEX, header_end(line), &parser.types, namespace.clone())?;
+    let data_type = value.get_type();
+    if !is_matchable(&data_type) {
+        return Err(NotMatchable(line.line_num, data_type.to_string()));
+    }
+
+    let arm_indent = line.indent + 1;
+    if parser.peek_line().is_none_or(|arm| arm.indent != arm_indent) {
+        return Err(IndentError(line.line_num + 1));
+    }
+
+    let mut match_node = MatchStatement::new(parser.void_type(), value);
+    let mut coverage = Coverage::new(data_type.clone());
+    while let Some(arm) = parser.peek_line() {
+        if arm.indent < arm_indent {
+            break;
+        }
+        if arm.indent > arm_indent {
+            return Err(IndentError(arm.line_num));
+        }
+        parser.line_index += 1;
+
+        let end = header_end(&arm);
+        let guard_index = find_token(&arm, 0, end, &[IF_TOKEN]);
+        let pattern = parse_pattern(&arm, 0, guard_index.unwrap_or(end), &parser.types, &data_type)?;
+        if let Some(unreachable) = coverage.unreachable(&pattern) {
+            return Err(UnreachablePattern(arm.line_num, unreachable));
+        }
+
+        let arm_namespace = Rc::new(RefCell::new(Namespace::with_parent(namespace.clone())));
+        for var in pattern.bindings() {
+            arm_namespace.borrow_mut().add_var(var);
+        }
+        let guard = match guard_index {
+            Some(guard_index) => Some(parser.condition_node(&arm, guard_index + 1, &arm_namespace)?),
+            None => None,
+        };
+        let body = parser.parse_indented_block(&arm, &arm_namespace)?;
+
+        if guard.is_none() {
+            coverage.add(pattern.clone());
+        }
+        match_node.add_arm(pattern, guard, body);
+    }
+
+    if let Some(missing) = coverage.missing() {
+        return Err(NonExhaustiveMatch(line.line_num, missing));
+    }
+
+    Ok(Box::new(match_node))
+}
+
+fn parse_while_loop(parser: &mut Parser,
+                    line: &Line,
+                    namespace: &Rc<RefCell<Namespace>>,
+                    label: Option<Rc<String>>,
+                    condition_start: usize
+) -> ParseResult {
+    let condition = parser.condition_node(line, condition_start, namespace)?;
+
+    parser.loop_labels.push(label.clone());
+    let body = parser.parse_child_block(line, namespace)?;
+    parser.loop_labels.pop();
+
+    Ok(Box::new(WhileLoop::new(parser.void_type(), label, condition, body)))
+}
+
+fn while_loop_node(parser: &mut Parser, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const CONDITION_START_INDEX: usize = 1;
+    parse_while_loop(parser, line, namespace, None, CONDITION_START_INDEX)
+}
+
+fn find_token(line: &Line, start: usize, end: usize, tokens: &[&str]) -> Option<usize> {
+    (start..end).find(|&i| tokens.contains(&line[i].as_str()))
+}
+
+// `for i in start..end`, `for i in start..=end` and `for i in start..end step n`
+fn parse_for_loop(parser: &mut Parser,
+                  line: &Line,
+                  namespace: &Rc<RefCell<Namespace>>,
+                  label: Option<Rc<String>>,
+                  var_index: usize
+) -> ParseResult {
+    assert_has_min_tokens(var_index + 3, line);
+
+    let var_name = line[var_index].clone();
+    assert_valid_symbol(var_name.clone(), line);
+    if line[var_index + 1].as_str() != IN_TOKEN {
+        return Err(UnexpectedToken(line.line_num, IN_TOKEN.to_string()));
+    }
+
+    let end = header_end(line);
+    let start_index = var_index + 2;
+    let (var_type, source) = match find_token(line, start_index, end, &[RANGE_TOKEN, INCLUSIVE_RANGE_TOKEN]) {
+        Some(range_index) => {
+            let (var_type, range) = range_source(parser, line, namespace, start_index, range_index, end)?;
+            (var_type, LoopSource::Range(range))
+        }
+        None => {
+            let slice = expression_node(line, start_index, end, &parser.types, namespace.clone())?;
+            let element_type = slice.get_type().element_type()
+                .ok_or(NotIterable(line.line_num, slice.get_type().to_string()))?;
+            (element_type, LoopSource::Slice(slice))
+        }
+    };
+
+    let var = VarNode::new(var_type, var_name, Mutability::Final);
+    let loop_namespace = Rc::new(RefCell::new(Namespace::with_parent(namespace.clone())));
+    loop_namespace.borrow_mut().add_var(var.clone());
+
+    parser.loop_labels.push(label.clone());
+    let body = parser.parse_indented_block(line, &loop_namespace)?;
+    parser.loop_labels.pop();
+
+    Ok(Box::new(ForLoop::new(parser.void_type(), label, var, source, body)))
+}
+
+fn range_source(parser: &Parser,
+                line: &Line,
+                namespace: &Rc<RefCell<Namespace>>,
+                start_index: usize,
+                range_index: usize,
+                end: usize
+) -> Result<(Rc<Type>, Range), CompilerError> {
+    let step_index = find_token(line, range_index, end, &[STEP_TOKEN]);
+
+    let types = &parser.types;
+    let mut start = expression_node(line, start_index, range_index, types, namespace.clone())?;
+    let mut stop = expression_node(line, range_index + 1, step_index.unwrap_or(end), types, namespace.clone())?;
+    // An unsuffixed literal bound takes the type of the other one before either is widened
+    adopt_literal_type(&mut stop, &start.get_type());
+    adopt_literal_type(&mut start, &stop.get_type());
+    if let Some(common) = common_type(&start.get_type(), &stop.get_type(), types) {
+        start = coerce(start, &common);
+        stop = coerce(stop, &common);
+    }
+    let step = match step_index {
+        Some(step_index) => {
+            Some(typed_expression_node(line, step_index + 1, end, types, namespace.clone(), &start.get_type())?)
+        }
+        None => None,
+    };
+    // Ranges only count up, so a step known to be zero or negative never reaches the stop
+    let step_value = step.as_ref().and_then(|step| step.integer_value());
+    if step_value.is_some_and(|value| value <= 0) {
+        let step_text: Vec<&str> = line.tokens[step_index.unwrap() + 1..end].iter().map(|token| token.as_str()).collect();
+        return Err(InvalidStep(line.line_num, step_text.join(" ")));
+    }
+
+    let var_type = start.get_type();
+    let bound_types = std::iter::once(stop.get_type()).chain(step.iter().map(|step| step.get_type()));
+    for bound_type in bound_types {
+        if !var_type.is_integer() || bound_type != var_type {
+            return Err(RangeTypeError(line.line_num, var_type.to_string(), bound_type.to_string()));
+        }
+    }
+
+    let range = Range::new(start, stop, step, line[range_index].as_str() == INCLUSIVE_RANGE_TOKEN);
+    Ok((var_type, range))
+}
+
+fn for_loop_node(parser: &mut Parser, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const VAR_INDEX: usize = 1;
+    parse_for_loop(parser, line, namespace, None, VAR_INDEX)
+}
+
+// `label: while cond` and `label: for i in range`
+fn labeled_statement_node(parser: &mut Parser, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const LABEL_INDEX: usize = 0;
+    const KEYWORD_INDEX: usize = 2;
+
+    let label = line[LABEL_INDEX].clone();
+    assert_valid_symbol(label.clone(), line);
+
+    match line[KEYWORD_INDEX].as_str() {
+        WHILE_TOKEN => parse_while_loop(parser, line, namespace, Some(label), KEYWORD_INDEX + 1),
+        FOR_TOKEN => parse_for_loop(parser, line, namespace, Some(label), KEYWORD_INDEX + 1),
+        _ => Err(LabelWithoutLoop(line.line_num, label.to_string())),
+    }
+}
+
+fn loop_control_node(parser: &mut Parser, line: &Line, _: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const MAX_TOKENS: usize = 2;
+    const LABEL_INDEX: usize = 1;
+
+    let keyword = line.get_token(0);
+    if line.len() > MAX_TOKENS {
+        return Err(InvalidExpression(line.line_num));
+    }
+    if parser.loop_labels.is_empty() {
+        return Err(LoopControlOutsideLoop(line.line_num, keyword.to_string()));
+    }
+
+    let label = line.tokens.get(LABEL_INDEX).cloned();
+    if let Some(label) = &label {
+        if !parser.loop_labels.contains(&Some(label.clone())) {
+            return Err(UndefinedLabel(line.line_num, label.to_string()));
+        }
+    }
+
+    let kind = if keyword == BREAK_TOKEN {
+        LoopControlKind::Break
+    } else {
+        LoopControlKind::Continue
+    };
+    Ok(Box::new(LoopControl::new(parser.void_type(), kind, label)))
+}
+
+fn function_def_node(parser: &mut Parser, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+    let header = header_line(line);
+    let mut function = Function::from(&header, &parser.types).unwrap();
+    function.parse_defaults(&header, &parser.types, namespace)?;
+    if line.indent > 0 {
+        declare_function(&function, line, namespace)?;
+    }
+    let function_namespace = function.namespace.clone();
+    function_namespace.borrow_mut().parent = Some(namespace.clone());
+
+    // Loops around the definition can't be targeted from inside the body
+    let enclosing_loops = std::mem::take(&mut parser.loop_labels);
+    parser.return_types.push(function.ret_type());
+    let body = parser.parse_indented_block(line, &function_namespace)?;
+    parser.return_types.pop();
+    parser.loop_labels = enclosing_loops;
+
+    if function.ret_type().name() != VOID_TYPE && !block_always_returns(&body) {
+        return Err(MissingReturn(line.line_num, function.name().to_string()));
+    }
+
+    function.set_body(body);
+    Ok(Box::new(function))
+}
+
+fn return_node(parser: &mut Parser, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const VALUE_START_INDEX: usize = 1;
+
+    let ret_type = parser.return_types.last()
+        .cloned()
+        .ok_or(ReturnOutsideFunction(line.line_num))?;
+
+    let value = if line.len() > VALUE_START_INDEX {
+        let value = typed_expression_node(line, VALUE_START_INDEX, line.len(), &parser.types, namespace.clone(), &ret_type)?;
+        Some(convert(value, &ret_type, line.line_num)?)
+    } else {
+        None
+    };
+
+    let value_type = value.as_ref().map_or(parser.void_type(), |value| value.get_type());
+    if value_type != ret_type {
+        return Err(ReturnTypeError(line.line_num, ret_type.to_string(), value_type.to_string()));
+    }
+
+    Ok(Box::new(ReturnStatement::new(parser.void_type(), value)))
+}
+
+// Struct and enum types are registered before parsing, so the definition only skips over the body
+fn type_definition_node(parser: &mut Parser, line: &Line, _: &Rc<RefCell<Namespace>>) -> ParseResult {
+    const NAME_INDEX: usize = 1;
+
+    if line.indent > 0 {
+        return Err(CompilerError::InvalidDefinition(line.line_num));
+    }
+    while parser.peek_line().is_some_and(|field_line| field_line.indent > line.indent) {
+        parser.line_index += 1;
+    }
+
+    let defined_type = parser.types[line.get_token(NAME_INDEX).as_str()].clone();
+    Ok(Box::new(TypeDefinition::new(parser.void_type(), defined_type)))
+}
+
+fn misplaced_branch(_: &mut Parser, line: &Line, _: &Rc<RefCell<Namespace>>) -> ParseResult {
+    Err(MisplacedBranch(line.line_num, line.get_token(0).to_string()))
+}
+
+// Block headers may end with an optional `:`
+fn header_end(line: &Line) -> usize {
+    match line.tokens.last() {
+        Some(token) if token.as_str() == BLOCK_START_TOKEN => line.len() - 1,
+        _ => line.len(),
+    }
+}
+
+fn header_line<'a>(line: &Line<'a>) -> Line<'a> {
+    Line { tokens: &line.tokens[..header_end(line)], ..*line }
+}
+
+fn binding_mutability(token: &str) -> Option<Mutability> {
+    match token {
+        CONST_TOKEN => Some(Mutability::Const),
+        FINAL_TOKEN => Some(Mutability::Final),
+        _ => None,
+    }
+}
+
+fn var_def_node(data_type: Option<Rc<Type>>,
+                mutability: Mutability,
+                name_index: usize,
+                line: &Line,
+                types: &HashMap<String, Rc<Type>>,
+                namespace: &Rc<RefCell<Namespace>>
+) -> ParseResult {
+    let var_name = line[name_index].clone();
+    assert_unique_var(var_name.clone(), line, namespace.clone());
+
+    let value = match &data_type {
+        Some(data_type) => typed_expression_node(line, name_index + 2, line.len(), types, namespace.clone(), data_type)?,
+        None => expression_node(line, name_index + 2, line.len(), types, namespace.clone())?,
+    };
+    if value.get_type().name() == VOID_TYPE {
+        return Err(VoidValue(line.line_num, var_name.to_string()));
+    }
+
+    let data_type = data_type.unwrap_or(value.get_type());
+    let value = convert(value, &data_type, line.line_num)?;
+    if value.get_type() != data_type {
+        return Err(
+            AssignmentTypeError(line.line_num, var_name.to_string(), data_type.to_string(), value.get_type().to_string())
+        );
+    }
+    if mutability == Mutability::Const && !value.is_constant() {
+        return Err(NonConstantExpression(line.line_num, var_name.to_string()));
+    }
+
+    let var_node = match mutability {
+        Mutability::Const => VarNode::constant(data_type, var_name, value.integer_value()),
+        _ => VarNode::new(data_type, var_name, mutability),
+    };
+    namespace.borrow_mut().add_var(var_node.clone());
+    Ok(Box::new(VarDefinition::new(var_node, value)))
+}
+
+fn symbol_definition(data_type: Option<Rc<Type>>,
+                     mutability: Mutability,
+                     name_index: usize,
+                     curr_line: &Line,
+                     types: &HashMap<String, Rc<Type>>,
+                     namespace: &Rc<RefCell<Namespace>>
+) -> ParseResult {
+    assert_has_min_tokens(name_index + 2, curr_line);
+
+    let symbol = curr_line[name_index].clone();
+    assert_valid_symbol(symbol, curr_line);
+
+    let token = &curr_line[name_index + 1];
+    match token.as_str() {
+        ASSIGNMENT_TOKEN => {
+            var_def_node(data_type, mutability, name_index, curr_line, types, namespace)
+        }
+        _ => {
+            Err(CompilerError::InvalidDefinition(curr_line.line_num))
         }
     }
 }
+
+fn is_function_def(line: &Line, types: &HashMap<String, Rc<Type>>) -> bool {
+    parse_type(line.tokens, 0, types).is_some_and(|(_, name_index)| {
+        line.len() > name_index + 1 && line[name_index + 1].as_str() == PAREN_OPEN_TOKEN
+    })
+}
+
+fn declare_function(function: &Function, line: &Line, namespace: &Rc<RefCell<Namespace>>) -> Result<(), CompilerError> {
+    let signature = function.signature();
+    if namespace.borrow().contains_overload(&signature) {
+        return Err(SymbolAlreadyDefined(line.line_num, signature.to_string()));
+    }
+
+    namespace.borrow_mut().add_function(signature);
+    Ok(())
+}
+
+// Name of a `struct Name:` or `enum Name:` header with a non-empty body
+fn type_header_name(header: &Line,
+                    body: &[Line],
+                    types: &HashMap<String, Rc<Type>>
+) -> Result<Rc<String>, CompilerError> {
+    const TYPE_HEADER_TOKENS: usize = 2;
+    const NAME_INDEX: usize = 1;
+
+    let header = header_line(header);
+    if header.len() != TYPE_HEADER_TOKENS {
+        return Err(CompilerError::InvalidDefinition(header.line_num));
+    }
+    let name = header[NAME_INDEX].clone();
+    assert_valid_symbol(name.clone(), &header);
+    if types.contains_key(name.as_str()) {
+        return Err(SymbolAlreadyDefined(header.line_num, name.to_string()));
+    }
+    if body.is_empty() {
+        return Err(IndentError(header.line_num + 1));
+    }
+
+    Ok(name)
+}
+
+// `struct Name:` followed by one `type name` line per field
+fn struct_type(header: &Line, field_lines: &[Line], types: &HashMap<String, Rc<Type>>) -> Result<Type, CompilerError> {
+    let name = type_header_name(header, field_lines, types)?;
+
+    let mut fields: Vec<(Rc<String>, Rc<Type>)> = Vec::with_capacity(field_lines.len());
+    for line in field_lines {
+        if line.indent != header.indent + 1 {
+            return Err(IndentError(line.line_num));
+        }
+
+        let (field_type, name_index) = parse_type(line.tokens, 0, types)
+            .ok_or(CompilerError::UndefinedType(line.line_num, line.get_token(0).to_string()))?;
+        if line.len() != name_index + 1 || field_type.name() == VOID_TYPE {
+            return Err(CompilerError::InvalidDefinition(line.line_num));
+        }
+
+        let field_name = line[name_index].clone();
+        assert_valid_symbol(field_name.clone(), line);
+        if fields.iter().any(|(name, _)| *name == field_name) {
+            return Err(SymbolAlreadyDefined(line.line_num, field_name.to_string()));
+        }
+        fields.push((field_name, field_type));
+    }
+
+    Ok(Type::structure(name.to_string(), fields))
+}
+
+// `Name` or `Name(type name, ...)` on a single variant line
+fn parse_variant(line: &Line, types: &HashMap<String, Rc<Type>>) -> Result<VariantDeclaration, CompilerError> {
+    let name = line[0].clone();
+    assert_valid_symbol(name.clone(), line);
+
+    let mut fields: Vec<(Rc<String>, Rc<Type>)> = Vec::new();
+    if line.len() == 1 {
+        return Ok((name, fields));
+    }
+    if line.get_token(1) != PAREN_OPEN_TOKEN || line.get_token(line.len() - 1) != PAREN_CLOSE_TOKEN {
+        return Err(CompilerError::InvalidDefinition(line.line_num));
+    }
+
+    let mut index = 2;
+    while index < line.len() - 1 {
+        let (field_type, name_index) = parse_type(line.tokens, index, types)
+            .ok_or(CompilerError::UndefinedType(line.line_num, line.get_token(index).to_string()))?;
+        if name_index >= line.len() - 1 || field_type.name() == VOID_TYPE {
+            return Err(CompilerError::InvalidDefinition(line.line_num));
+        }
+
+        let field_name = line[name_index].clone();
+        assert_valid_symbol(field_name.clone(), line);
+        if fields.iter().any(|(name, _)| *name == field_name) {
+            return Err(SymbolAlreadyDefined(line.line_num, field_name.to_string()));
+        }
+        fields.push((field_name, field_type));
+
+        index = name_index + 1;
+        match line.get_token(index).as_str() {
+            PARAM_DELIMITER if index + 1 < line.len() - 1 => index += 1,
+            PAREN_CLOSE_TOKEN if index == line.len() - 1 => {}
+            _ => return Err(CompilerError::InvalidDefinition(line.line_num)),
+        }
+    }
+
+    Ok((name, fields))
+}
+
+// `enum Name:` followed by one variant per line
+fn enum_type(header: &Line, variant_lines: &[Line], types: &HashMap<String, Rc<Type>>) -> Result<Type, CompilerError> {
+    let name = type_header_name(header, variant_lines, types)?;
+
+    let mut variants: Vec<VariantDeclaration> = Vec::with_capacity(variant_lines.len());
+    for line in variant_lines {
+        if line.indent != header.indent + 1 {
+            return Err(IndentError(line.line_num));
+        }
+
+        let variant = parse_variant(line, types)?;
+        if variants.iter().any(|(name, _)| *name == variant.0) {
+            return Err(SymbolAlreadyDefined(line.line_num, variant.0.to_string()));
+        }
+        variants.push(variant);
+    }
+
+    Ok(Type::enumeration(name.to_string(), variants))
+}
+
+// User types are registered in source order, so a field can only use types declared above it
+fn declare_types(lines: &[Line], types: &mut HashMap<String, Rc<Type>>) -> Result<(), CompilerError> {
+    for (i, header) in lines.iter().enumerate() {
+        if header.indent > 0 {
+            continue;
+        }
+
+        let body_len = lines[i + 1..].iter().take_while(|line| line.indent > header.indent).count();
+        let body = &lines[i + 1..i + 1 + body_len];
+        let user_type = match header.get_token(0).as_str() {
+            STRUCT_TOKEN => struct_type(header, body, types)?,
+            ENUM_TOKEN => enum_type(header, body, types)?,
+            _ => continue,
+        };
+        types.insert(user_type.name().to_string(), Rc::new(user_type));
+    }
+
+    Ok(())
+}
+
+// Top level functions are declared up front so they can be called before their definition
+fn declare_top_level_functions(lines: &[Line],
+                               types: &HashMap<String, Rc<Type>>,
+                               namespace: &Rc<RefCell<Namespace>>
+) -> Result<(), CompilerError> {
+    for line in lines.iter().filter(|line| line.indent == 0 && is_function_def(line, types)) {
+        let function = Function::from(&header_line(line), types).unwrap();
+        declare_function(&function, line, namespace)?;
+    }
+
+    Ok(())
+}
+
+fn parse_program(lines: Vec<Line>) -> Result<Block, CompilerError> {
+    let mut types = compile_native_types();
+    let namespace = Rc::new(RefCell::new(Namespace::new()));
+
+    declare_types(&lines, &mut types)?;
+    declare_top_level_functions(&lines, &types, &namespace)?;
+
+    let mut parser = Parser::new(lines, types);
+    parser.parse_block(0, &namespace)
+}
+
+pub fn generate_ast(file_name: &String) {
+    let tokens = tokenize_file(file_name).unwrap();
+    let lines: Vec<Line> = LineIterator::new(&tokens)
+        .filter(|line| line.start < line.end)
+        .collect();
+
+    match parse_program(lines) {
+        Ok(block) => {
+            block.iter().for_each(|node| println!("{}", node.dump()));
+        }
+        Err(e) => raise_compiler_error(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler_error::CompilerError::{ImmutableAssignment, TypeAsValue, UnknownVariant};
+    use crate::data_type::{I16_TYPE, I64_TYPE, I8_TYPE, U8_TYPE};
+    use crate::line::with_lines;
+
+    // Type of the loop variable of `for i in <range>:`, with `a` and `b` in scope
+    fn range_type(range: &str, a_type: &str, b_type: &str) -> String {
+        const START_INDEX: usize = 3;
+        let types = compile_native_types();
+        let namespace = Rc::new(RefCell::new(Namespace::new()));
+        for (name, type_name) in [("a", a_type), ("b", b_type)] {
+            let var = VarNode::new(types[type_name].clone(), Rc::new(name.to_string()), Mutability::Final);
+            namespace.borrow_mut().add_var(var);
+        }
+
+        with_lines(&format!("for i in {range}:"), |lines| {
+            let line = lines[0];
+            let parser = Parser::new(lines, types);
+            let end = header_end(&line);
+            let range_index = find_token(&line, START_INDEX, end, &[RANGE_TOKEN, INCLUSIVE_RANGE_TOKEN]).unwrap();
+            let (var_type, _) = range_source(&parser, &line, &namespace, START_INDEX, range_index, end).unwrap();
+            var_type.to_string()
+        })
+    }
+
+    fn parsed(code: &str) -> Result<Vec<String>, CompilerError> {
+        with_lines(code, |lines| parse_program(lines).map(|block| block.iter().map(|node| node.dump()).collect()))
+    }
+
+    // Dump of the nested function `inner` up to its body, which lists its captures
+    fn inner_function(code: &str) -> String {
+        let outer = parsed(code).unwrap().pop().unwrap();
+        let start = outer.find("(fn inner").unwrap();
+        let end = outer[start..].find(" [").unwrap();
+        outer[start..start + end].to_string()
+    }
+
+    #[test]
+    fn nested_function_captures_enclosing_locals() {
+        let code = "\
+void outer(i64 a):
+    i64 b = 1
+    final i64 c = 2
+    void inner():
+        i64 d = a + b + c
+";
+        assert_eq!(inner_function(code), "(fn inner () (captures mut a mut b c)");
+    }
+
+    #[test]
+    fn parameters_and_locals_shadow_captures() {
+        let code = "\
+void outer(i64 a):
+    i64 b = 1
+    void inner(i64 a):
+        i64 b = a
+        i64 c = b
+";
+        assert_eq!(inner_function(code), "(fn inner (a)");
+    }
+
+    #[test]
+    fn captures_cross_nested_blocks() {
+        let code = "\
+void outer(bool f):
+    if f:
+        i64 a = 1
+        void inner():
+            while f:
+                a = a + 1
+";
+        assert_eq!(inner_function(code), "(fn inner () (captures mut f mut a)");
+    }
+
+    #[test]
+    fn captures_pass_through_intermediate_functions() {
+        let code = "\
+void outer():
+    i64 a = 1
+    void middle():
+        void inner():
+            i64 b = a
+";
+        let outer = parsed(code).unwrap().pop().unwrap();
+        assert!(outer.contains("(fn middle () (captures mut a)"), "{outer}");
+        assert!(outer.contains("(fn inner () (captures mut a)"), "{outer}");
+    }
+
+    #[test]
+    fn globals_are_not_captured() {
+        let code = "\
+i64 g = 1
+void outer():
+    void inner():
+        i64 b = g
+";
+        assert_eq!(inner_function(code), "(fn inner ()");
+    }
+
+    #[test]
+    fn immutable_captures_cannot_be_assigned() {
+        let code = "\
+void outer():
+    final i64 a = 1
+    void inner():
+        a = 2
+";
+        assert!(matches!(parsed(code), Err(ImmutableAssignment(..))));
+    }
+
+    #[test]
+    fn constant_steps_must_be_positive() {
+        let program = |step: &str| parsed(&format!(
+            "const i64 z = 0\nconst i8 n = -2\ni64 m = 0\nfor i in 0..10 step {step}:\n    i64 j = i\n"
+        ));
+        for step in ["0", "-1", "00", "z", "n", "2 - 3", "z * 5", "n as i64"] {
+            assert!(matches!(program(step), Err(InvalidStep(..))), "step `{step}` accepted");
+        }
+        for step in ["1", "z + 1", "n * -1", "m", "200 as i8"] {
+            assert!(program(step).is_ok(), "step `{step}` rejected");
+        }
+    }
+
+    #[test]
+    fn statement_may_start_with_type_name() {
+        let types = "\
+struct P:
+    u8 a
+    i64 b
+enum E:
+    A
+    B(i64 v)
+";
+        let dumps = parsed(&format!("{types}E.A\nE.B(3)\nP(1, 2)\nP p = P(1, 2)\n")).unwrap();
+        assert_eq!(dumps[2..], ["(E.A)", "(E.B 3)", "(P 1 2)", "(let p (P 1 2))"]);
+
+        assert!(matches!(parsed(&format!("{types}E.C\n")), Err(UnknownVariant(..))));
+        assert!(matches!(parsed(&format!("{types}P.a\n")), Err(TypeAsValue(..))));
+        assert!(matches!(parsed(&format!("{types}i64\n")), Err(TypeAsValue(..))));
+    }
+
+    #[test]
+    fn literal_bound_takes_type_of_other_bound() {
+        assert_eq!(range_type("0..a", U8_TYPE, U8_TYPE), U8_TYPE);
+        assert_eq!(range_type("a..=10", I8_TYPE, I8_TYPE), I8_TYPE);
+        assert_eq!(range_type("0..a step 2", I16_TYPE, I16_TYPE), I16_TYPE);
+        assert_eq!(range_type("0..10", U8_TYPE, U8_TYPE), I64_TYPE);
+    }
+
+    #[test]
+    fn bounds_widen_to_common_type() {
+        assert_eq!(range_type("a..b", I8_TYPE, I16_TYPE), I16_TYPE);
+        assert_eq!(range_type("a..b", I16_TYPE, U8_TYPE), I16_TYPE);
+        assert_eq!(range_type("a..b", U8_TYPE, I8_TYPE), I16_TYPE);
+    }
+}
@@ -1,78 +1,227 @@
-use std::cell::{Ref, RefCell};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use crate::data_type::Type;
-use crate::function_node::Function;
+use crate::conversion::CastKind;
+use crate::data_type::{Field, Type};
+use crate::function_node::FunctionSignature;
+use crate::pattern::Pattern;
 
-pub struct SourceFile {
-    functions: Vec<Function>
-}
+pub type Block = Vec<Box<dyn ASTNode>>;
 
-impl SourceFile {
-    pub fn new() -> Self {
-        Self { functions: Vec::new() }
+// Written for an argument left to its parameter's default
+const DEFAULT_ARG: &str = "_";
+
+pub trait ASTNode {
+    fn get_type(&self) -> Rc<Type>;
+
+    // The node as an s-expression, such as `(+ a (* b c))`
+    fn dump(&self) -> String;
+
+    fn is_lvalue(&self) -> bool {
+        false
+    }
+
+    fn is_mutable(&self) -> bool {
+        false
+    }
+
+    fn is_constant(&self) -> bool {
+        false
+    }
+
+    fn always_returns(&self) -> bool {
+        false
     }
 
-    pub fn add_function(&mut self, function: Function) {
-        self.functions.push(function);
+    // Only unsuffixed literals can change type to fit their context
+    fn can_adopt_type(&self, _: &Type) -> bool {
+        false
+    }
+
+    fn adopt_type(&mut self, _: Rc<Type>) {}
+
+    fn as_literal(&self) -> Option<&Literal> {
+        None
+    }
+
+    // Value of a constant integer expression that folds without overflow
+    fn integer_value(&self) -> Option<i128> {
+        None
     }
 }
 
-pub trait ASTNode {
-    fn get_type(&self) -> Rc<Type>;
+// Retypes `node` as `target` where that makes an otherwise mismatched literal fit
+pub fn adopt_literal_type(node: &mut Box<dyn ASTNode>, target: &Rc<Type>) {
+    if node.get_type() != *target && node.can_adopt_type(target) {
+        node.adopt_type(target.clone());
+    }
+}
+
+pub fn block_always_returns(block: &Block) -> bool {
+    block.iter().any(|node| node.always_returns())
+}
+
+pub fn dump_block(block: &Block) -> String {
+    let nodes: Vec<String> = block.iter().map(|node| node.dump()).collect();
+    format!("[{}]", nodes.join("; "))
+}
+
+fn list(items: Vec<String>) -> String {
+    format!("({})", items.join(" "))
+}
+
+fn dump_label(label: &Option<Rc<String>>) -> Option<String> {
+    label.as_ref().map(|label| format!("{label}:"))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mutability {
+    Mutable,
+    Final,
+    Const,
 }
 
 #[derive(Debug)]
 pub struct VarNode {
     data_type: Rc<Type>,
     name: Rc<String>,
+    mutability: Mutability,
+    // Folded value of a `const` integer
+    value: Option<i128>,
 }
 
 impl ASTNode for VarNode {
     fn get_type(&self) -> Rc<Type> {
         self.data_type.clone()
     }
+
+    fn dump(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn is_lvalue(&self) -> bool {
+        true
+    }
+
+    fn is_mutable(&self) -> bool {
+        self.mutability == Mutability::Mutable
+    }
+
+    fn is_constant(&self) -> bool {
+        self.mutability == Mutability::Const
+    }
+
+    fn integer_value(&self) -> Option<i128> {
+        self.value
+    }
 }
 
 impl VarNode {
-    pub fn new(data_type: Rc<Type>, name: Rc<String>) -> Self {
-        Self { data_type, name }
+    pub fn new(data_type: Rc<Type>, name: Rc<String>, mutability: Mutability) -> Self {
+        Self { data_type, name, mutability, value: None }
+    }
+
+    pub fn constant(data_type: Rc<Type>, name: Rc<String>, value: Option<i128>) -> Self {
+        Self { value, ..Self::new(data_type, name, Mutability::Const) }
+    }
+
+    pub fn name(&self) -> Rc<String> {
+        self.name.clone()
     }
 }
 
 impl Clone for VarNode {
     fn clone(&self) -> Self {
-        Self::new(self.data_type.clone(), self.name.clone())
+        Self { value: self.value, ..Self::new(self.data_type.clone(), self.name.clone(), self.mutability) }
+    }
+}
+
+pub struct VarDefinition {
+    var: VarNode,
+    value: Box<dyn ASTNode>,
+}
+
+impl VarDefinition {
+    pub fn new(var: VarNode, value: Box<dyn ASTNode>) -> Self {
+        Self { var, value }
+    }
+}
+
+impl ASTNode for VarDefinition {
+    fn get_type(&self) -> Rc<Type> {
+        self.var.get_type()
+    }
+
+    fn dump(&self) -> String {
+        list(vec!["let".to_string(), self.var.name.to_string(), self.value.dump()])
     }
 }
 
 pub struct Literal {
     data_type: Rc<Type>,
     value: Rc<String>,
+    suffixed: bool,
 }
 
 impl ASTNode for Literal {
     fn get_type(&self) -> Rc<Type> {
         self.data_type.clone()
     }
+
+    fn dump(&self) -> String {
+        self.value.to_string()
+    }
+
+    fn is_constant(&self) -> bool {
+        true
+    }
+
+    fn can_adopt_type(&self, data_type: &Type) -> bool {
+        !self.suffixed && self.data_type.same_kind(data_type) && data_type.is_valid_literal(&self.value)
+    }
+
+    fn adopt_type(&mut self, data_type: Rc<Type>) {
+        self.data_type = data_type;
+    }
+
+    fn as_literal(&self) -> Option<&Literal> {
+        Some(self)
+    }
+
+    fn integer_value(&self) -> Option<i128> {
+        self.data_type.is_integer().then(|| self.value.parse().ok())?
+    }
 }
 
 impl Literal {
-    pub fn new(data_type: Rc<Type>, value: Rc<String>) -> Self {
-        Self { data_type, value }
+    pub fn new(data_type: Rc<Type>, value: Rc<String>, suffixed: bool) -> Self {
+        Self { data_type, value, suffixed }
+    }
+
+    // Value without its suffix, with a folded minus sign
+    pub fn value(&self) -> Rc<String> {
+        self.value.clone()
+    }
+
+    pub fn is_suffixed(&self) -> bool {
+        self.suffixed
     }
 }
 
 pub struct BinaryOperator {
     data_type: Rc<Type>,
+    operator: Rc<String>,
     left: Box<dyn ASTNode>,
     right: Box<dyn ASTNode>,
 }
 
 impl BinaryOperator {
-    pub fn new(left: Box<dyn ASTNode>, right: Box<dyn ASTNode>) -> Self {
-        let l = left.get_type();
-        Self { data_type: l, left, right }
+    pub fn new(operator: Rc<String>,
+               data_type: Rc<Type>,
+               left: Box<dyn ASTNode>,
+               right: Box<dyn ASTNode>
+    ) -> Self {
+        Self { data_type, operator, left, right }
     }
 }
 
@@ -80,41 +229,752 @@ impl ASTNode for BinaryOperator {
     fn get_type(&self) -> Rc<Type> {
         self.data_type.clone()
     }
+
+    fn dump(&self) -> String {
+        list(vec![self.operator.to_string(), self.left.dump(), self.right.dump()])
+    }
+
+    fn is_constant(&self) -> bool {
+        self.left.is_constant() && self.right.is_constant()
+    }
+
+    fn integer_value(&self) -> Option<i128> {
+        let (left, right) = (self.left.integer_value()?, self.right.integer_value()?);
+        let value = match self.operator.as_str() {
+            "+" => left.checked_add(right),
+            "-" => left.checked_sub(right),
+            "*" => left.checked_mul(right),
+            "/" => left.checked_div(right),
+            "%" => left.checked_rem(right),
+            _ => None,
+        }?;
+        fits_type(value, &self.data_type)
+    }
+}
+
+// `value` if it is a valid integer of `data_type`
+fn fits_type(value: i128, data_type: &Type) -> Option<i128> {
+    (data_type.is_integer() && data_type.is_valid_literal(&value.to_string())).then_some(value)
+}
+
+pub struct Assignment {
+    target: Box<dyn ASTNode>,
+    value: Box<dyn ASTNode>,
+}
+
+impl Assignment {
+    pub fn new(target: Box<dyn ASTNode>, value: Box<dyn ASTNode>) -> Self {
+        Self { target, value }
+    }
+}
+
+impl ASTNode for Assignment {
+    fn get_type(&self) -> Rc<Type> {
+        self.target.get_type()
+    }
+
+    fn dump(&self) -> String {
+        list(vec!["=".to_string(), self.target.dump(), self.value.dump()])
+    }
+}
+
+// && and || only evaluate the right operand when the left one doesn't decide the result
+pub struct LogicalOperator {
+    data_type: Rc<Type>,
+    operator: Rc<String>,
+    left: Box<dyn ASTNode>,
+    right: Box<dyn ASTNode>,
+}
+
+impl LogicalOperator {
+    pub fn new(operator: Rc<String>,
+               data_type: Rc<Type>,
+               left: Box<dyn ASTNode>,
+               right: Box<dyn ASTNode>
+    ) -> Self {
+        Self { data_type, operator, left, right }
+    }
+}
+
+impl ASTNode for LogicalOperator {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        list(vec![self.operator.to_string(), self.left.dump(), self.right.dump()])
+    }
+
+    fn is_constant(&self) -> bool {
+        self.left.is_constant() && self.right.is_constant()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShiftKind {
+    Left,
+    ArithmeticRight,
+    LogicalRight,
+}
+
+pub struct ShiftOperator {
+    data_type: Rc<Type>,
+    kind: ShiftKind,
+    value: Box<dyn ASTNode>,
+    amount: Box<dyn ASTNode>,
+}
+
+impl ShiftOperator {
+    pub fn new(kind: ShiftKind,
+               data_type: Rc<Type>,
+               value: Box<dyn ASTNode>,
+               amount: Box<dyn ASTNode>
+    ) -> Self {
+        Self { data_type, kind, value, amount }
+    }
+}
+
+impl ASTNode for ShiftOperator {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        list(vec![format!("{:?}", self.kind), self.value.dump(), self.amount.dump()])
+    }
+
+    fn is_constant(&self) -> bool {
+        self.value.is_constant() && self.amount.is_constant()
+    }
+}
+
+pub struct UnaryOperator {
+    data_type: Rc<Type>,
+    operator: Rc<String>,
+    operand: Box<dyn ASTNode>,
+}
+
+impl UnaryOperator {
+    pub fn new(operator: Rc<String>, data_type: Rc<Type>, operand: Box<dyn ASTNode>) -> Self {
+        Self { data_type, operator, operand }
+    }
+}
+
+impl ASTNode for UnaryOperator {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        list(vec![self.operator.to_string(), self.operand.dump()])
+    }
+
+    fn is_constant(&self) -> bool {
+        self.operand.is_constant()
+    }
+
+    fn integer_value(&self) -> Option<i128> {
+        match self.operator.as_str() {
+            "-" => fits_type(self.operand.integer_value()?.checked_neg()?, &self.data_type),
+            _ => None,
+        }
+    }
+}
+
+pub struct Cast {
+    data_type: Rc<Type>,
+    kind: CastKind,
+    value: Box<dyn ASTNode>,
+}
+
+impl Cast {
+    pub fn new(data_type: Rc<Type>, kind: CastKind, value: Box<dyn ASTNode>) -> Self {
+        Self { data_type, kind, value }
+    }
+}
+
+impl ASTNode for Cast {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        list(vec![format!("{:?}", self.kind), self.data_type.to_string(), self.value.dump()])
+    }
+
+    fn is_constant(&self) -> bool {
+        self.value.is_constant()
+    }
+
+    // Only casts that keep every value keep a folded one
+    fn integer_value(&self) -> Option<i128> {
+        match self.kind {
+            CastKind::Identity | CastKind::SignExtend | CastKind::ZeroExtend => self.value.integer_value(),
+            _ => None,
+        }
+    }
+}
+
+// Struct or enum declaration
+pub struct TypeDefinition {
+    data_type: Rc<Type>,
+    defined_type: Rc<Type>,
+}
+
+impl TypeDefinition {
+    pub fn new(data_type: Rc<Type>, defined_type: Rc<Type>) -> Self {
+        Self { data_type, defined_type }
+    }
+}
+
+impl ASTNode for TypeDefinition {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        list(vec!["type".to_string(), self.defined_type.to_string()])
+    }
+}
+
+// Field values in declaration order
+pub struct StructLiteral {
+    data_type: Rc<Type>,
+    fields: Vec<Box<dyn ASTNode>>,
+}
+
+impl StructLiteral {
+    pub fn new(data_type: Rc<Type>, fields: Vec<Box<dyn ASTNode>>) -> Self {
+        Self { data_type, fields }
+    }
+}
+
+impl ASTNode for StructLiteral {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let mut items = vec![self.data_type.to_string()];
+        items.extend(self.fields.iter().map(|field| field.dump()));
+        list(items)
+    }
+
+    fn is_constant(&self) -> bool {
+        self.fields.iter().all(|field| field.is_constant())
+    }
+}
+
+pub struct VariantLiteral {
+    data_type: Rc<Type>,
+    discriminant: usize,
+    payload: Vec<Box<dyn ASTNode>>,
+}
+
+impl VariantLiteral {
+    pub fn new(data_type: Rc<Type>, discriminant: usize, payload: Vec<Box<dyn ASTNode>>) -> Self {
+        Self { data_type, discriminant, payload }
+    }
+}
+
+impl ASTNode for VariantLiteral {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let variant = &self.data_type.variants().unwrap()[self.discriminant];
+        let mut items = vec![format!("{}.{}", self.data_type, variant.name)];
+        items.extend(self.payload.iter().map(|field| field.dump()));
+        list(items)
+    }
+
+    fn is_constant(&self) -> bool {
+        self.payload.iter().all(|field| field.is_constant())
+    }
+}
+
+pub struct FieldAccess {
+    data_type: Rc<Type>,
+    base: Box<dyn ASTNode>,
+    field: Rc<String>,
+    offset: usize,
+}
+
+impl FieldAccess {
+    pub fn new(base: Box<dyn ASTNode>, field: &Field) -> Self {
+        Self { data_type: field.data_type.clone(), base, field: field.name.clone(), offset: field.offset }
+    }
+}
+
+impl ASTNode for FieldAccess {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        list(vec![".".to_string(), self.base.dump(), format!("{}@{}", self.field, self.offset)])
+    }
+
+    fn is_lvalue(&self) -> bool {
+        self.base.is_lvalue()
+    }
+
+    fn is_mutable(&self) -> bool {
+        self.base.is_mutable()
+    }
+
+    fn is_constant(&self) -> bool {
+        self.base.is_constant()
+    }
+}
+
+pub struct IfStatement {
+    data_type: Rc<Type>,
+    branches: Vec<(Box<dyn ASTNode>, Block)>,
+    else_body: Option<Block>,
+}
+
+impl IfStatement {
+    pub fn new(data_type: Rc<Type>, condition: Box<dyn ASTNode>, body: Block) -> Self {
+        Self { data_type, branches: vec![(condition, body)], else_body: None }
+    }
+
+    pub fn add_elif(&mut self, condition: Box<dyn ASTNode>, body: Block) {
+        self.branches.push((condition, body));
+    }
+
+    pub fn set_else(&mut self, body: Block) {
+        self.else_body = Some(body);
+    }
+}
+
+impl ASTNode for IfStatement {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let mut items = vec!["if".to_string()];
+        for (condition, body) in &self.branches {
+            items.push(condition.dump());
+            items.push(dump_block(body));
+        }
+        if let Some(else_body) = &self.else_body {
+            items.push("else".to_string());
+            items.push(dump_block(else_body));
+        }
+        list(items)
+    }
+
+    fn always_returns(&self) -> bool {
+        match &self.else_body {
+            Some(else_body) => {
+                block_always_returns(else_body)
+                    && self.branches.iter().all(|(_, body)| block_always_returns(body))
+            }
+            None => false,
+        }
+    }
+}
+
+struct MatchArm {
+    pattern: Pattern,
+    guard: Option<Box<dyn ASTNode>>,
+    body: Block,
+}
+
+pub struct MatchStatement {
+    data_type: Rc<Type>,
+    value: Box<dyn ASTNode>,
+    arms: Vec<MatchArm>,
+}
+
+impl MatchStatement {
+    pub fn new(data_type: Rc<Type>, value: Box<dyn ASTNode>) -> Self {
+        Self { data_type, value, arms: Vec::new() }
+    }
+
+    pub fn add_arm(&mut self, pattern: Pattern, guard: Option<Box<dyn ASTNode>>, body: Block) {
+        self.arms.push(MatchArm { pattern, guard, body });
+    }
+}
+
+impl ASTNode for MatchStatement {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let value_type = self.value.get_type();
+        let mut items = vec!["match".to_string(), self.value.dump()];
+        items.extend(self.arms.iter().map(|arm| {
+            let mut arm_items = vec![arm.pattern.describe(&value_type)];
+            if let Some(guard) = &arm.guard {
+                arm_items.push("if".to_string());
+                arm_items.push(guard.dump());
+            }
+            arm_items.push(dump_block(&arm.body));
+            list(arm_items)
+        }));
+        list(items)
+    }
+
+    // Matches are checked to be exhaustive, so one arm always runs
+    fn always_returns(&self) -> bool {
+        self.arms.iter().all(|arm| block_always_returns(&arm.body))
+    }
+}
+
+pub struct WhileLoop {
+    data_type: Rc<Type>,
+    label: Option<Rc<String>>,
+    condition: Box<dyn ASTNode>,
+    body: Block,
+}
+
+impl WhileLoop {
+    pub fn new(data_type: Rc<Type>,
+               label: Option<Rc<String>>,
+               condition: Box<dyn ASTNode>,
+               body: Block
+    ) -> Self {
+        Self { data_type, label, condition, body }
+    }
+}
+
+impl ASTNode for WhileLoop {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let mut items = vec!["while".to_string()];
+        items.extend(dump_label(&self.label));
+        items.push(self.condition.dump());
+        items.push(dump_block(&self.body));
+        list(items)
+    }
+}
+
+// Counts up from `start` by `step`, 1 by default, while below `end`, or up to it if inclusive.
+// A constant step must be positive.
+pub struct Range {
+    start: Box<dyn ASTNode>,
+    end: Box<dyn ASTNode>,
+    step: Option<Box<dyn ASTNode>>,
+    inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: Box<dyn ASTNode>,
+               end: Box<dyn ASTNode>,
+               step: Option<Box<dyn ASTNode>>,
+               inclusive: bool
+    ) -> Self {
+        Self { start, end, step, inclusive }
+    }
+
+    fn dump(&self) -> String {
+        let operator = if self.inclusive { "..=" } else { ".." };
+        let mut items = vec![operator.to_string(), self.start.dump(), self.end.dump()];
+        items.extend(self.step.iter().map(|step| step.dump()));
+        list(items)
+    }
+}
+
+pub enum LoopSource {
+    Range(Range),
+    Slice(Box<dyn ASTNode>),
+}
+
+pub struct ForLoop {
+    data_type: Rc<Type>,
+    label: Option<Rc<String>>,
+    var: VarNode,
+    source: LoopSource,
+    body: Block,
+}
+
+impl ForLoop {
+    pub fn new(data_type: Rc<Type>,
+               label: Option<Rc<String>>,
+               var: VarNode,
+               source: LoopSource,
+               body: Block
+    ) -> Self {
+        Self { data_type, label, var, source, body }
+    }
+}
+
+impl ASTNode for ForLoop {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let mut items = vec!["for".to_string()];
+        items.extend(dump_label(&self.label));
+        items.push(self.var.name.to_string());
+        items.push(match &self.source {
+            LoopSource::Range(range) => range.dump(),
+            LoopSource::Slice(slice) => slice.dump(),
+        });
+        items.push(dump_block(&self.body));
+        list(items)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LoopControlKind {
+    Break,
+    Continue,
+}
+
+pub struct LoopControl {
+    data_type: Rc<Type>,
+    kind: LoopControlKind,
+    label: Option<Rc<String>>,
+}
+
+impl LoopControl {
+    pub fn new(data_type: Rc<Type>, kind: LoopControlKind, label: Option<Rc<String>>) -> Self {
+        Self { data_type, kind, label }
+    }
+}
+
+impl ASTNode for LoopControl {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let keyword = match self.kind {
+            LoopControlKind::Break => "break",
+            LoopControlKind::Continue => "continue",
+        };
+        let mut items = vec![keyword.to_string()];
+        items.extend(dump_label(&self.label));
+        list(items)
+    }
+}
+
+pub struct ReturnStatement {
+    data_type: Rc<Type>,
+    value: Option<Box<dyn ASTNode>>,
+}
+
+impl ReturnStatement {
+    pub fn new(data_type: Rc<Type>, value: Option<Box<dyn ASTNode>>) -> Self {
+        Self { data_type, value }
+    }
+}
+
+impl ASTNode for ReturnStatement {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let mut items = vec!["return".to_string()];
+        items.extend(self.value.iter().map(|value| value.dump()));
+        list(items)
+    }
+
+    fn always_returns(&self) -> bool {
+        true
+    }
+}
+
+// Arguments are ordered by parameter, None where the parameter's default is used
+pub struct FunctionCall {
+    data_type: Rc<Type>,
+    function: Rc<FunctionSignature>,
+    args: Vec<Option<Box<dyn ASTNode>>>,
+}
+
+impl FunctionCall {
+    pub fn new(function: Rc<FunctionSignature>, args: Vec<Option<Box<dyn ASTNode>>>) -> Self {
+        Self { data_type: function.ret_type.clone(), function, args }
+    }
+}
+
+impl ASTNode for FunctionCall {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let mut items = vec![self.function.name.to_string()];
+        items.extend(self.args.iter().map(|arg| arg.as_ref().map_or(DEFAULT_ARG.to_string(), |arg| arg.dump())));
+        list(items)
+    }
+}
+
+// A named function used as a value rather than called directly
+pub struct FunctionRef {
+    data_type: Rc<Type>,
+    function: Rc<FunctionSignature>,
+}
+
+impl FunctionRef {
+    pub fn new(function: Rc<FunctionSignature>) -> Self {
+        let data_type = Rc::new(Type::function(function.param_types.clone(), function.ret_type.clone()));
+        Self { data_type, function }
+    }
+}
+
+impl ASTNode for FunctionRef {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        self.function.name.to_string()
+    }
+}
+
+// Call through a value of function type
+pub struct IndirectCall {
+    data_type: Rc<Type>,
+    callee: Box<dyn ASTNode>,
+    args: Vec<Box<dyn ASTNode>>,
+}
+
+impl IndirectCall {
+    pub fn new(data_type: Rc<Type>, callee: Box<dyn ASTNode>, args: Vec<Box<dyn ASTNode>>) -> Self {
+        Self { data_type, callee, args }
+    }
+}
+
+impl ASTNode for IndirectCall {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let mut items = vec!["call".to_string(), self.callee.dump()];
+        items.extend(self.args.iter().map(|arg| arg.dump()));
+        list(items)
+    }
+}
+
+// Arguments collected by a variadic parameter, passed to the callee as a single slice
+pub struct SliceLiteral {
+    data_type: Rc<Type>,
+    elements: Vec<Box<dyn ASTNode>>,
+}
+
+impl SliceLiteral {
+    pub fn new(data_type: Rc<Type>, elements: Vec<Box<dyn ASTNode>>) -> Self {
+        Self { data_type, elements }
+    }
+}
+
+impl ASTNode for SliceLiteral {
+    fn get_type(&self) -> Rc<Type> {
+        self.data_type.clone()
+    }
+
+    fn dump(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|element| element.dump()).collect();
+        format!("[{}]", elements.join(" "))
+    }
 }
 
 #[derive(Debug)]
 pub struct Namespace {
     vars: HashMap<Rc<String>, VarNode>,
+    functions: HashMap<Rc<String>, Vec<Rc<FunctionSignature>>>,
+    // Only set for the outermost namespace of a function
+    captures: Option<Vec<VarNode>>,
     pub parent: Option<Rc<RefCell<Namespace>>>
 }
 
 impl Namespace {
     pub fn new() -> Self {
-        Self { vars: HashMap::new(), parent: None }
+        Self { vars: HashMap::new(), functions: HashMap::new(), captures: None, parent: None }
+    }
+
+    pub fn with_parent(parent: Rc<RefCell<Namespace>>) -> Self {
+        Self { parent: Some(parent), ..Self::new() }
+    }
+
+    pub fn for_function() -> Self {
+        Self { captures: Some(Vec::new()), ..Self::new() }
     }
 
     pub fn contains_var(&self, var_name: Rc<String>) -> bool {
         self.vars.contains_key(&var_name)
     }
 
-    pub fn get_var(&self, var_name: &String) -> Option<VarNode> {
-        if let Some(var) = self.vars.get(var_name) {
-            return Some(var.clone())
-        }
+    // A local of an enclosing function is captured by every function between it and the use.
+    // Globals live in the root namespace and are never captured.
+    pub fn get_var(namespace: &Rc<RefCell<Namespace>>, var_name: &String) -> Option<VarNode> {
+        let mut crossed_functions = Vec::new();
 
-        let mut namespace_opt = self.parent.clone();
+        let mut namespace_opt = Some(namespace.clone());
         while let Some(namespace) = namespace_opt {
-            let curr_namespace =  namespace.borrow();
+            let curr_namespace = namespace.borrow();
             if let Some(var) = curr_namespace.vars.get(var_name) {
+                if curr_namespace.parent.is_some() {
+                    for function_namespace in crossed_functions {
+                        Namespace::add_capture(&function_namespace, var);
+                    }
+                }
                 return Some(var.clone());
             }
+
+            if curr_namespace.captures.is_some() {
+                crossed_functions.push(namespace.clone());
+            }
             namespace_opt = curr_namespace.parent.clone();
         }
 
         None
     }
 
+    fn add_capture(namespace: &Rc<RefCell<Namespace>>, var: &VarNode) {
+        if let Some(captures) = namespace.borrow_mut().captures.as_mut() {
+            if !captures.iter().any(|capture| capture.name == var.name) {
+                captures.push(var.clone());
+            }
+        }
+    }
+
+    pub fn captures(&self) -> Vec<VarNode> {
+        self.captures.clone().unwrap_or_default()
+    }
+
     pub fn add_var(&mut self, var_node: VarNode) {
         self.vars.insert(var_node.name.clone(), var_node);
     }
+
+    pub fn contains_overload(&self, signature: &FunctionSignature) -> bool {
+        self.functions.get(&signature.name).is_some_and(|overloads| {
+            overloads.iter().any(|overload| overload.param_types == signature.param_types)
+        })
+    }
+
+    // Overloads from the innermost namespace that defines `name`, which hide any outer ones
+    pub fn get_overloads(&self, name: &String) -> Option<Vec<Rc<FunctionSignature>>> {
+        if let Some(overloads) = self.functions.get(name) {
+            return Some(overloads.clone());
+        }
+
+        let mut namespace_opt = self.parent.clone();
+        while let Some(namespace) = namespace_opt {
+            let curr_namespace = namespace.borrow();
+            if let Some(overloads) = curr_namespace.functions.get(name) {
+                return Some(overloads.clone());
+            }
+            namespace_opt = curr_namespace.parent.clone();
+        }
+
+        None
+    }
+
+    pub fn add_function(&mut self, signature: Rc<FunctionSignature>) {
+        self.functions.entry(signature.name.clone()).or_default().push(signature);
+    }
 }
@@ -6,8 +6,14 @@ pub enum CompilerError {
     InvalidDefinition(usize),
     #[error("Error: Line {0}: Invalid Symbol `{1}`")]
     InvalidSymbol(usize, String),
-    #[error("Error: Line {0}: Invalid Assignment")]
-    InvalidAssignment(usize),
+    #[error("Error: Line {0}: Cannot assign to `{1}`")]
+    InvalidAssignment(usize, String),
+    #[error("Error: Line {0}: Cannot assign to immutable binding `{1}`")]
+    ImmutableAssignment(usize, String),
+    #[error("Error: Line {0}: Value of `{1}` is not a constant expression")]
+    NonConstantExpression(usize, String),
+    #[error("Error: Line {0}: Cannot assign `{3}` to `{1}` of type `{2}`")]
+    AssignmentTypeError(usize, String, String, String),
     #[error("Error: Line {0}: Invalid Expression")]
     InvalidExpression(usize),
     #[error("Error: Line {0}: Type `{1}` is not defined")]
@@ -20,9 +26,93 @@ pub enum CompilerError {
     MismatchedParentheses(usize),
     #[error("Error: Line {0}: Cannot apply `{1}` to `{2}` and `{3}`")]
     BinaryOperatorTypeError(usize, String, String, String),
+    #[error("Error: Line {0}: Condition must be `bool`, found `{1}`")]
+    ConditionTypeError(usize, String),
+    #[error("Error: Line {0}: `{1}` without a matching `if`")]
+    MisplacedBranch(usize, String),
+    #[error("Error: Line {0}: `{1}` outside of a loop")]
+    LoopControlOutsideLoop(usize, String),
+    #[error("Error: Line {0}: Loop label `{1}` is not defined")]
+    UndefinedLabel(usize, String),
+    #[error("Error: Line {0}: Label `{1}` must precede a loop")]
+    LabelWithoutLoop(usize, String),
+    #[error("Error: Line {0}: Range bounds `{1}` and `{2}` must be the same integer type")]
+    RangeTypeError(usize, String, String),
+    #[error("Error: Line {0}: Loop step `{1}` must be positive")]
+    InvalidStep(usize, String),
+    #[error("Error: Line {0}: Cannot iterate over `{1}`")]
+    NotIterable(usize, String),
+    #[error("Error: Line {0}: `return` outside of a function")]
+    ReturnOutsideFunction(usize),
+    #[error("Error: Line {0}: Expected return type `{1}`, found `{2}`")]
+    ReturnTypeError(usize, String, String),
+    #[error("Error: Line {0}: Function `{1}` does not return a value on every path")]
+    MissingReturn(usize, String),
+    #[error("Error: Line {0}: Function `{1}` expects {2} arguments, found {3}")]
+    ArgumentCountError(usize, String, usize, usize),
+    #[error("Error: Line {0}: Argument `{2}` of `{1}` must be `{3}`, found `{4}`")]
+    ArgumentTypeError(usize, String, String, String, String),
+    #[error("Error: Line {0}: Missing argument `{2}` in call to `{1}`")]
+    MissingArgument(usize, String, String),
+    #[error("Error: Line {0}: `{1}` has no parameter named `{2}`")]
+    UnknownArgument(usize, String, String),
+    #[error("Error: Line {0}: Argument `{2}` of `{1}` is passed more than once")]
+    DuplicateArgument(usize, String, String),
+    #[error("Error: Line {0}: Positional argument follows a named argument in call to `{1}`")]
+    PositionalAfterNamed(usize, String),
+    #[error("Error: Line {0}: Parameter `{1}` without a default follows a defaulted parameter")]
+    RequiredAfterDefault(usize, String),
+    #[error("Error: Line {0}: `{1}` has no field `{2}`")]
+    UnknownField(usize, String, String),
+    #[error("Error: Line {0}: `{1}` has no variant `{2}`")]
+    UnknownVariant(usize, String, String),
+    #[error("Error: Line {0}: Cannot match on `{1}`")]
+    NotMatchable(usize, String),
+    #[error("Error: Line {0}: Invalid pattern `{1}`")]
+    InvalidPattern(usize, String),
+    #[error("Error: Line {0}: Pattern `{1}` cannot match `{2}`")]
+    PatternTypeError(usize, String, String),
+    #[error("Error: Line {0}: `{1}` has {2} fields, found {3} patterns")]
+    PatternFieldCount(usize, String, usize, usize),
+    #[error("Error: Line {0}: `{1}` must be bound with the same type in every alternative")]
+    InconsistentBinding(usize, String),
+    #[error("Error: Line {0}: Pattern `{1}` is unreachable")]
+    UnreachablePattern(usize, String),
+    #[error("Error: Line {0}: Match is not exhaustive, `{1}` is not covered")]
+    NonExhaustiveMatch(usize, String),
+    #[error("Error: Line {0}: Cannot cast `{1}` to `{2}`")]
+    InvalidCast(usize, String, String),
+    #[error("Error: Line {0}: Literal `{1}` is out of range for `{2}`")]
+    LiteralOutOfRange(usize, String, String),
+    #[error("Error: Line {0}: Cannot call `{1}` of type `{2}`")]
+    NotCallable(usize, String, String),
+    #[error("Error: Line {0}: Overloaded function `{1}` cannot be used as a value")]
+    OverloadedFunctionValue(usize, String),
+    #[error("Error: Line {0}: Variadic parameter `{1}` must be the last parameter and cannot have a default")]
+    InvalidVariadic(usize, String),
+    #[error("Error: Line {0}: No overload of `{1}` accepts `({2})`, candidates: {3}")]
+    NoMatchingOverload(usize, String, String, String),
+    #[error("Error: Line {0}: Call to `{1}` is ambiguous, candidates: {2}")]
+    AmbiguousCall(usize, String, String),
+    #[error("Error: Line {0}: `{1}` cannot hold a `void` value")]
+    VoidValue(usize, String),
+    #[error("Error: Line {0}: Type `{1}` cannot be used as a value")]
+    TypeAsValue(usize, String),
+    #[error("Error: Line {0}: Cannot apply `{1}` to `{2}`")]
+    UnaryOperatorTypeError(usize, String, String),
 }
 
-pub fn raise_compiler_error(e: CompilerError) {
+#[derive(thiserror::Error, Debug)]
+pub enum CompilerWarning {
+    #[error("Warning: Line {0}: Converting `{1}` to `{2}` may lose data, write `as {2}` if it is intended")]
+    LossyConversion(usize, String, String),
+}
+
+pub fn report_compiler_warning(warning: CompilerWarning) {
+    println!("{warning}");
+}
+
+pub fn raise_compiler_error(e: CompilerError) -> ! {
     println!("{e}");
     std::process::exit(1);
 }
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::ast_node::{adopt_literal_type, ASTNode, Cast};
+use crate::compiler_error::{report_compiler_warning, CompilerError};
+use crate::compiler_error::CompilerError::LiteralOutOfRange;
+use crate::compiler_error::CompilerWarning::LossyConversion;
+use crate::data_type::{Type, BOOL_TYPE, CHAR_TYPE, F32_TYPE, F64_TYPE, I16_TYPE, I32_TYPE, I64_TYPE, I8_TYPE, U16_TYPE, U32_TYPE, U64_TYPE, U8_TYPE};
+
+// How the bits of a value change when it is cast
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CastKind {
+    Identity,
+    // Same size integers of different signedness keep their bits
+    Reinterpret,
+    // Keeps the low bits
+    Truncate,
+    SignExtend,
+    ZeroExtend,
+    IntToFloat,
+    // Rounds toward zero and saturates at the bounds of the target, NaN becomes 0
+    FloatToInt,
+    FloatConvert,
+}
+
+#[derive(Copy, Clone)]
+enum CastClass {
+    Integer,
+    Float,
+    Char,
+    Bool,
+    Byte,
+}
+
+impl CastClass {
+    fn contains(self, data_type: &Type) -> bool {
+        match self {
+            CastClass::Integer => data_type.is_integer(),
+            CastClass::Float => data_type.is_float(),
+            CastClass::Char => data_type.name() == CHAR_TYPE,
+            CastClass::Bool => data_type.name() == BOOL_TYPE,
+            CastClass::Byte => data_type.name() == U8_TYPE,
+        }
+    }
+}
+
+// Source and target classes of every allowed cast besides casting a type to itself
+const CASTS: [(CastClass, CastClass); 7] = [
+    (CastClass::Integer, CastClass::Integer),
+    (CastClass::Integer, CastClass::Float),
+    (CastClass::Float, CastClass::Integer),
+    (CastClass::Float, CastClass::Float),
+    (CastClass::Char, CastClass::Integer),
+    (CastClass::Bool, CastClass::Integer),
+    (CastClass::Byte, CastClass::Char),
+];
+
+// Char and bool convert through their unsigned code
+fn is_signed(data_type: &Type) -> bool {
+    data_type.is_signed_integer() || data_type.is_float()
+}
+
+fn numeric_cast_kind(from: &Type, to: &Type) -> CastKind {
+    match (from.is_float(), to.is_float()) {
+        (true, true) => CastKind::FloatConvert,
+        (true, false) => CastKind::FloatToInt,
+        (false, true) => CastKind::IntToFloat,
+        (false, false) if from.size() > to.size() => CastKind::Truncate,
+        (false, false) if from.size() == to.size() => CastKind::Reinterpret,
+        (false, false) if is_signed(from) => CastKind::SignExtend,
+        (false, false) => CastKind::ZeroExtend,
+    }
+}
+
+pub fn cast_kind(from: &Rc<Type>, to: &Rc<Type>) -> Option<CastKind> {
+    if from == to {
+        return Some(CastKind::Identity);
+    }
+
+    CASTS.iter()
+        .any(|(source, target)| source.contains(from) && target.contains(to))
+        .then(|| numeric_cast_kind(from, to))
+}
+
+// Coercion lattice: a value converts implicitly along these edges, and transitively beyond
+// them, because every value of the source type is exactly representable in the target.
+//
+//   i8 -> i16 -> i32 -> i64     f32 -> f64
+//          ^      ^      ^
+//   u8 -> u16 -> u32 -> u64
+//
+// Any other numeric conversion that `as` allows, such as i64 -> i32 or f64 -> i64, is lossy.
+// It is still made implicitly, but with a warning that an `as` silences, so the loss is always
+// either pointed out or spelled out in the source.
+const WIDENINGS: [(&str, &str); 10] = [
+    (I8_TYPE, I16_TYPE),
+    (I16_TYPE, I32_TYPE),
+    (I32_TYPE, I64_TYPE),
+    (U8_TYPE, U16_TYPE),
+    (U16_TYPE, U32_TYPE),
+    (U32_TYPE, U64_TYPE),
+    (U8_TYPE, I16_TYPE),
+    (U16_TYPE, I32_TYPE),
+    (U32_TYPE, I64_TYPE),
+    (F32_TYPE, F64_TYPE),
+];
+
+// `from` and every type it widens to, narrowest first
+fn widened_types(from: &str) -> Vec<&str> {
+    let mut widened = vec![from];
+    let mut i = 0;
+    while i < widened.len() {
+        for &(source, target) in &WIDENINGS {
+            if source == widened[i] && !widened.contains(&target) {
+                widened.push(target);
+            }
+        }
+        i += 1;
+    }
+
+    widened
+}
+
+pub fn widens_to(from: &Type, to: &Type) -> bool {
+    from != to && widened_types(from.name()).contains(&to.name())
+}
+
+// Narrowest type both operands widen to, e.g. `i16` for `i8` and `u8`
+pub fn common_type(left: &Type,
+                   right: &Type,
+                   types: &HashMap<String, Rc<Type>>
+) -> Option<Rc<Type>> {
+    let right_widened = widened_types(right.name());
+    widened_types(left.name())
+        .into_iter()
+        .filter(|name| right_widened.contains(name))
+        .min_by_key(|name| types[*name].size())
+        .map(|name| types[name].clone())
+}
+
+pub fn can_coerce(node: &dyn ASTNode, target: &Rc<Type>) -> bool {
+    node.get_type() == *target || node.can_adopt_type(target) || widens_to(&node.get_type(), target)
+}
+
+// Fits `node` to `target` by retyping a literal or widening it, leaving it unchanged otherwise
+pub fn coerce(mut node: Box<dyn ASTNode>, target: &Rc<Type>) -> Box<dyn ASTNode> {
+    adopt_literal_type(&mut node, target);
+
+    let from = node.get_type();
+    match cast_kind(&from, target) {
+        Some(kind) if widens_to(&from, target) => Box::new(Cast::new(target.clone(), kind, node)),
+        _ => node,
+    }
+}
+
+fn is_lossy_conversion(from: &Rc<Type>, to: &Rc<Type>) -> bool {
+    from.is_numeric() && to.is_numeric() && !widens_to(from, to) && cast_kind(from, to).is_some()
+}
+
+pub fn can_convert(node: &dyn ASTNode, target: &Rc<Type>) -> bool {
+    can_coerce(node, target) || is_lossy_conversion(&node.get_type(), target)
+}
+
+// Fits `node` to `target` like `coerce`, also making lossy numeric conversions with a warning.
+// The node is returned unchanged if it can't be converted, which the caller reports. An
+// unsuffixed literal that doesn't fit `target` is an error rather than a lossy conversion.
+pub fn convert(node: Box<dyn ASTNode>,
+               target: &Rc<Type>,
+               line_num: usize
+) -> Result<Box<dyn ASTNode>, CompilerError> {
+    let node = coerce(node, target);
+    let from = node.get_type();
+    if from == *target {
+        return Ok(node);
+    }
+
+    let literal = node.as_literal().filter(|literal| !literal.is_suffixed() && from.same_kind(target));
+    if let Some(literal) = literal {
+        return Err(LiteralOutOfRange(line_num, literal.value().to_string(), target.to_string()));
+    }
+
+    match cast_kind(&from, target) {
+        Some(kind) if is_lossy_conversion(&from, target) => {
+            report_compiler_warning(LossyConversion(line_num, from.to_string(), target.to_string()));
+            Ok(Box::new(Cast::new(target.clone(), kind, node)))
+        }
+        _ => Ok(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_type::{compile_native_types, STR_TYPE};
+
+    fn kind(from: &str, to: &str) -> Option<CastKind> {
+        let types = compile_native_types();
+        cast_kind(&types[from], &types[to])
+    }
+
+    #[test]
+    fn integer_casts_extend_or_truncate() {
+        assert_eq!(kind(I64_TYPE, I8_TYPE), Some(CastKind::Truncate));
+        assert_eq!(kind(U8_TYPE, I64_TYPE), Some(CastKind::ZeroExtend));
+        assert_eq!(kind(I8_TYPE, U64_TYPE), Some(CastKind::SignExtend));
+        assert_eq!(kind(I32_TYPE, U32_TYPE), Some(CastKind::Reinterpret));
+        assert_eq!(kind(I16_TYPE, I16_TYPE), Some(CastKind::Identity));
+    }
+
+    #[test]
+    fn float_casts() {
+        assert_eq!(kind(F64_TYPE, I32_TYPE), Some(CastKind::FloatToInt));
+        assert_eq!(kind(U16_TYPE, F32_TYPE), Some(CastKind::IntToFloat));
+        assert_eq!(kind(F32_TYPE, F64_TYPE), Some(CastKind::FloatConvert));
+    }
+
+    #[test]
+    fn char_and_bool_casts_use_their_code() {
+        assert_eq!(kind(BOOL_TYPE, I64_TYPE), Some(CastKind::ZeroExtend));
+        assert_eq!(kind(CHAR_TYPE, U8_TYPE), Some(CastKind::Truncate));
+        assert_eq!(kind(U8_TYPE, CHAR_TYPE), Some(CastKind::ZeroExtend));
+    }
+
+    #[test]
+    fn disallowed_casts() {
+        assert_eq!(kind(STR_TYPE, I64_TYPE), None);
+        assert_eq!(kind(I64_TYPE, STR_TYPE), None);
+        assert_eq!(kind(I64_TYPE, BOOL_TYPE), None);
+        assert_eq!(kind(U16_TYPE, CHAR_TYPE), None);
+        assert_eq!(kind(F64_TYPE, CHAR_TYPE), None);
+    }
+}
@@ -1,12 +1,98 @@
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::rc::Rc;
+use std::str::FromStr;
+
+pub const I8_TYPE: &str = "i8";
+pub const I16_TYPE: &str = "i16";
+pub const I32_TYPE: &str = "i32";
+pub const I64_TYPE: &str = "i64";
+pub const U8_TYPE: &str = "u8";
+pub const U16_TYPE: &str = "u16";
+pub const U32_TYPE: &str = "u32";
+pub const U64_TYPE: &str = "u64";
+pub const F32_TYPE: &str = "f32";
+pub const F64_TYPE: &str = "f64";
+pub const CHAR_TYPE: &str = "char";
+pub const BOOL_TYPE: &str = "bool";
+pub const STR_TYPE: &str = "str";
+pub const VOID_TYPE: &str = "void";
+
+const FUNCTION_TYPE_TOKEN: &str = "fn";
+const RETURN_TYPE_TOKEN: &str = "->";
+const PAREN_OPEN_TOKEN: &str = "(";
+const PAREN_CLOSE_TOKEN: &str = ")";
+const TYPE_DELIMITER: &str = ",";
+
+const SIGNED_INTEGER_TYPES: [&str; 4] = [I8_TYPE, I16_TYPE, I32_TYPE, I64_TYPE];
+const UNSIGNED_INTEGER_TYPES: [&str; 4] = [U8_TYPE, U16_TYPE, U32_TYPE, U64_TYPE];
+const FLOAT_TYPES: [&str; 2] = [F32_TYPE, F64_TYPE];
+const NUMERIC_SUFFIXES: [&str; 10] = [
+    I8_TYPE, I16_TYPE, I32_TYPE, I64_TYPE, U8_TYPE, U16_TYPE, U32_TYPE, U64_TYPE, F32_TYPE, F64_TYPE
+];
+
+type LiteralValidator = fn(&str) -> bool;
+
+#[derive(Debug)]
+pub struct Field {
+    pub name: Rc<String>,
+    pub data_type: Rc<Type>,
+    pub offset: usize,
+}
+
+// Variant name with its payload fields, before layout
+pub type VariantDeclaration = (Rc<String>, Vec<(Rc<String>, Rc<Type>)>);
+
+// Payload field offsets are from the start of the enum value, after the discriminant
+#[derive(Debug)]
+pub struct Variant {
+    pub name: Rc<String>,
+    pub discriminant: usize,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+enum TypeKind {
+    Scalar,
+    Slice(Rc<Type>),
+    Function(Vec<Rc<Type>>, Rc<Type>),
+    Struct(Vec<Field>),
+    Enum(Vec<Variant>),
+}
+
+// Lays fields out in declaration order from `start`, each at the next offset that suits its
+// alignment, and returns them with the end offset and largest alignment
+fn layout_fields(fields: Vec<(Rc<String>, Rc<Type>)>, start: usize) -> (Vec<Field>, usize, usize) {
+    let mut end = start;
+    let mut alignment: usize = 1;
+    let fields = fields.into_iter()
+        .map(|(name, data_type)| {
+            let offset = end.next_multiple_of(data_type.alignment);
+            end = offset + data_type.size;
+            alignment = alignment.max(data_type.alignment);
+            Field { name, data_type, offset }
+        })
+        .collect();
+
+    (fields, end, alignment)
+}
+
+// Smallest unsigned integer size that can number `variant_count` variants
+fn discriminant_size(variant_count: usize) -> usize {
+    match variant_count {
+        0..=0x100 => 1,
+        0x101..=0x1_0000 => 2,
+        _ => 4,
+    }
+}
 
 #[derive(Debug)]
 pub struct Type {
     name: String,
     size: usize,
-    validate_literal: fn(&str) -> bool,
+    alignment: usize,
+    validate_literal: LiteralValidator,
+    kind: TypeKind,
 }
 
 impl PartialEq<Self> for Type {
@@ -21,8 +107,47 @@ impl Display for Type {
     }
 }
 
-fn valid_i64_literal(literal: &str) -> bool {
-    literal.parse::<i64>().is_ok()
+fn valid_integer_literal<T: FromStr>(literal: &str) -> bool {
+    literal.parse::<T>().is_ok()
+}
+
+fn valid_f32_literal(literal: &str) -> bool {
+    literal.parse::<f32>().is_ok_and(f32::is_finite)
+}
+
+fn valid_f64_literal(literal: &str) -> bool {
+    literal.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+fn valid_char_literal(literal: &str) -> bool {
+    char_value(literal).is_some()
+}
+
+// Character written by a char literal such as `'a'` or `'\n'`
+pub fn char_value(literal: &str) -> Option<char> {
+    let inner = literal.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    match (chars.next()?, chars.next(), chars.next()) {
+        ('\\' | '\'', None, _) => None,
+        (char, None, _) => Some(char),
+        ('\\', Some(escape), None) => match escape {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' | '\'' => Some(escape),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn valid_bool_literal(literal: &str) -> bool {
+    literal == "true" || literal == "false"
+}
+
+fn no_literal(_: &str) -> bool {
+    false
 }
 
 fn valid_str_literal(literal: &str) -> bool {
@@ -31,33 +156,377 @@ fn valid_str_literal(literal: &str) -> bool {
     len > 1 && literal_bytes[0] == b'"' && literal_bytes[len - 1] == b'"'
 }
 
+// Integer arithmetic wraps on overflow. Division truncates toward zero and is signed or
+// unsigned according to the operand type.
 pub fn compile_native_types() -> HashMap<String, Rc<Type>> {
-    const NATIVE_TYPE_COUNT: usize = 2;
-    const NATIVE_TYPES: [(&str, usize, fn(&str) -> bool); NATIVE_TYPE_COUNT] = [
-        ("i64", 8, valid_i64_literal),
-        ("str", 8, valid_str_literal)
+    const NATIVE_TYPE_COUNT: usize = 14;
+    const NATIVE_TYPES: [(&str, usize, usize, LiteralValidator); NATIVE_TYPE_COUNT] = [
+        (I8_TYPE, 1, 1, valid_integer_literal::<i8>),
+        (I16_TYPE, 2, 2, valid_integer_literal::<i16>),
+        (I32_TYPE, 4, 4, valid_integer_literal::<i32>),
+        (I64_TYPE, 8, 8, valid_integer_literal::<i64>),
+        (U8_TYPE, 1, 1, valid_integer_literal::<u8>),
+        (U16_TYPE, 2, 2, valid_integer_literal::<u16>),
+        (U32_TYPE, 4, 4, valid_integer_literal::<u32>),
+        (U64_TYPE, 8, 8, valid_integer_literal::<u64>),
+        (F32_TYPE, 4, 4, valid_f32_literal),
+        (F64_TYPE, 8, 8, valid_f64_literal),
+        (CHAR_TYPE, 4, 4, valid_char_literal),
+        (BOOL_TYPE, 1, 1, valid_bool_literal),
+        (STR_TYPE, 8, 8, valid_str_literal),
+        (VOID_TYPE, 0, 1, no_literal)
     ];
 
     let mut types = HashMap::with_capacity(NATIVE_TYPE_COUNT);
-    NATIVE_TYPES.iter().for_each(|&(name, size, value)| {
-        types.insert(name.to_string(), Rc::new(Type::new(name.to_string(), size, value)));
+    NATIVE_TYPES.iter().for_each(|&(name, size, alignment, value)| {
+        types.insert(name.to_string(), Rc::new(Type::new(name.to_string(), size, alignment, value)));
     });
 
     types
 }
 
-pub fn get_literal_type(types: &HashMap<String, Rc<Type>>, literal: &str) -> Option<Rc<Type>> {
-    for data_type in types.values() {
-        if (data_type.validate_literal)(literal) {
-            return Some(data_type.clone());
+pub fn join_types(types: &[Rc<Type>]) -> String {
+    types.iter().map(|data_type| data_type.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+// Parses the type starting at `start`, either a type name or `fn(params) -> ret`, and returns
+// it along with the index of the token after it
+pub fn parse_type(tokens: &[Rc<String>],
+                  start: usize,
+                  types: &HashMap<String, Rc<Type>>
+) -> Option<(Rc<Type>, usize)> {
+    let token = tokens.get(start)?;
+    if token.as_str() != FUNCTION_TYPE_TOKEN {
+        return types.get(token.as_str()).map(|data_type| (data_type.clone(), start + 1));
+    }
+    if tokens.get(start + 1)?.as_str() != PAREN_OPEN_TOKEN {
+        return None;
+    }
+
+    let mut params = Vec::new();
+    let mut i = start + 2;
+    if tokens.get(i)?.as_str() == PAREN_CLOSE_TOKEN {
+        i += 1;
+    } else {
+        loop {
+            let (param, next) = parse_type(tokens, i, types)?;
+            if param.name() == VOID_TYPE {
+                return None;
+            }
+            params.push(param);
+
+            i = next + 1;
+            match tokens.get(next)?.as_str() {
+                TYPE_DELIMITER => continue,
+                PAREN_CLOSE_TOKEN => break,
+                _ => return None,
+            }
+        }
+    }
+
+    match tokens.get(i) {
+        Some(token) if token.as_str() == RETURN_TYPE_TOKEN => {
+            let (ret_type, next) = parse_type(tokens, i + 1, types)?;
+            Some((Rc::new(Type::function(params, ret_type)), next))
+        }
+        _ => Some((Rc::new(Type::function(params, types[VOID_TYPE].clone())), i)),
+    }
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+// `12`, `1.5`, `1e5` or `2.5E-3`, without a sign or suffix
+fn is_number(value: &str) -> bool {
+    let (mantissa, exponent) = match value.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent.strip_prefix(['-', '+']).unwrap_or(exponent))),
+        None => (value, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+
+    is_digits(integer) && fraction.is_none_or(is_digits) && exponent.is_none_or(is_digits)
+}
+
+// Type of a literal that has neither a suffix nor a usable expected type, None if it is not a
+// well-formed literal
+fn default_literal_type(literal: &str) -> Option<&'static str> {
+    let number = literal.strip_prefix('-').unwrap_or(literal);
+    match number.as_bytes().first()? {
+        b'0'..=b'9' => {
+            let (value, _) = split_suffix(number);
+            match is_number(value) {
+                true if value.contains(['.', 'e', 'E']) => Some(F64_TYPE),
+                true => Some(I64_TYPE),
+                false => None,
+            }
+        }
+        _ if number.len() < literal.len() => None,
+        b'"' => Some(STR_TYPE),
+        b'\'' => Some(CHAR_TYPE),
+        _ if valid_bool_literal(literal) => Some(BOOL_TYPE),
+        _ => None,
+    }
+}
+
+// Splits `5u8` into `5` and `u8`
+fn split_suffix(literal: &str) -> (&str, Option<&'static str>) {
+    for suffix in NUMERIC_SUFFIXES {
+        if let Some(value) = literal.strip_suffix(suffix) {
+            if value.ends_with(|c: char| c.is_ascii_digit()) {
+                return (value, Some(suffix));
+            }
         }
     }
 
-    None
+    (literal, None)
+}
+
+pub struct TypedLiteral {
+    pub data_type: Rc<Type>,
+    pub value: String,
+    pub suffixed: bool,
+}
+
+// A suffix decides the type of a literal. Otherwise an expected type of the same kind is used
+// if it can hold the value, falling back to the default for the kind of literal. For an operand
+// the expected type is only a hint, so places that convert the value to it report a literal
+// that doesn't fit. The value may not be valid for the chosen type, which the caller reports.
+pub fn type_literal(types: &HashMap<String, Rc<Type>>,
+                    literal: &str,
+                    expected: Option<&Rc<Type>>
+) -> Option<TypedLiteral> {
+    let default_type = &types[default_literal_type(literal)?];
+    let (value, suffix) = match default_type.is_numeric() {
+        true => split_suffix(literal),
+        false => (literal, None),
+    };
+
+    let data_type = match (suffix, expected) {
+        (Some(suffix), _) => types[suffix].clone(),
+        (None, Some(expected)) if default_type.same_kind(expected) && expected.is_valid_literal(value) => {
+            expected.clone()
+        }
+        (None, _) => default_type.clone(),
+    };
+
+    Some(TypedLiteral { data_type, value: value.to_string(), suffixed: suffix.is_some() })
 }
 
 impl Type {
-    pub fn new(name: String, size: usize, validate_literal: fn(&str) -> bool) -> Self {
-        Self { name, size, validate_literal }
+    pub fn new(name: String, size: usize, alignment: usize, validate_literal: LiteralValidator) -> Self {
+        Self { name, size, alignment, validate_literal, kind: TypeKind::Scalar }
+    }
+
+    // Pointer and length of a run of `element` values
+    pub fn slice(element: Rc<Type>) -> Self {
+        const SLICE_SIZE: usize = 16;
+        const SLICE_ALIGNMENT: usize = 8;
+        let name = format!("[{element}]");
+        let kind = TypeKind::Slice(element);
+        Self { name, size: SLICE_SIZE, alignment: SLICE_ALIGNMENT, validate_literal: no_literal, kind }
+    }
+
+    // Code pointer and pointer to the captured environment
+    pub fn function(params: Vec<Rc<Type>>, ret_type: Rc<Type>) -> Self {
+        const FUNCTION_SIZE: usize = 16;
+        const FUNCTION_ALIGNMENT: usize = 8;
+        let name = format!("{FUNCTION_TYPE_TOKEN}({}) {RETURN_TYPE_TOKEN} {ret_type}", join_types(&params));
+        let kind = TypeKind::Function(params, ret_type);
+        Self { name, size: FUNCTION_SIZE, alignment: FUNCTION_ALIGNMENT, validate_literal: no_literal, kind }
+    }
+
+    pub fn structure(name: String, fields: Vec<(Rc<String>, Rc<Type>)>) -> Self {
+        let (fields, end, alignment) = layout_fields(fields, 0);
+        let size = end.next_multiple_of(alignment);
+        Self { name, size, alignment, validate_literal: no_literal, kind: TypeKind::Struct(fields) }
+    }
+
+    // The discriminant comes first, followed by the payload of the active variant. Every
+    // payload starts at the same offset, aligned for the most aligned payload field.
+    pub fn enumeration(name: String, variants: Vec<VariantDeclaration>) -> Self {
+        let discriminant_size = discriminant_size(variants.len());
+        let alignment = variants.iter()
+            .flat_map(|(_, fields)| fields.iter().map(|(_, data_type)| data_type.alignment))
+            .fold(discriminant_size, usize::max);
+        let payload_offset = discriminant_size.next_multiple_of(alignment);
+
+        let mut end = discriminant_size;
+        let variants = variants.into_iter()
+            .enumerate()
+            .map(|(discriminant, (name, fields))| {
+                let (fields, payload_end, _) = layout_fields(fields, payload_offset);
+                end = end.max(payload_end);
+                Variant { name, discriminant, fields }
+            })
+            .collect();
+
+        let size = end.next_multiple_of(alignment);
+        Self { name, size, alignment, validate_literal: no_literal, kind: TypeKind::Enum(variants) }
+    }
+
+    pub fn variants(&self) -> Option<&[Variant]> {
+        match &self.kind {
+            TypeKind::Enum(variants) => Some(variants),
+            _ => None,
+        }
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants()?.iter().find(|variant| variant.name.as_str() == name)
+    }
+
+    pub fn fields(&self) -> Option<&[Field]> {
+        match &self.kind {
+            TypeKind::Struct(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields()?.iter().find(|field| field.name.as_str() == name)
+    }
+
+    pub fn element_type(&self) -> Option<Rc<Type>> {
+        match &self.kind {
+            TypeKind::Slice(element) => Some(element.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn function_signature(&self) -> Option<(&[Rc<Type>], Rc<Type>)> {
+        match &self.kind {
+            TypeKind::Function(params, ret_type) => Some((params, ret_type.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_signed_integer(&self) -> bool {
+        SIGNED_INTEGER_TYPES.contains(&self.name.as_str())
+    }
+
+    pub fn is_unsigned_integer(&self) -> bool {
+        UNSIGNED_INTEGER_TYPES.contains(&self.name.as_str())
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.is_signed_integer() || self.is_unsigned_integer()
+    }
+
+    pub fn is_float(&self) -> bool {
+        FLOAT_TYPES.contains(&self.name.as_str())
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    // Whether literals of one type can be retyped as the other
+    pub fn same_kind(&self, other: &Type) -> bool {
+        (self.is_integer() && other.is_integer()) || (self.is_float() && other.is_float())
+    }
+
+    pub fn is_valid_literal(&self, literal: &str) -> bool {
+        (self.validate_literal)(literal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal_type(literal: &str, expected: Option<&str>) -> String {
+        let types = compile_native_types();
+        let expected = expected.map(|name| types[name].clone());
+        type_literal(&types, literal, expected.as_ref()).unwrap().data_type.to_string()
+    }
+
+    #[test]
+    fn exponent_literals_are_floats() {
+        assert_eq!(literal_type("1e5", None), F64_TYPE);
+        assert_eq!(literal_type("2.5E-3", None), F64_TYPE);
+        assert_eq!(literal_type("1e3", Some(F32_TYPE)), F32_TYPE);
+        assert_eq!(literal_type("1e3f32", None), F32_TYPE);
+    }
+
+    #[test]
+    fn malformed_numbers_are_not_literals() {
+        let types = compile_native_types();
+        for literal in ["1else", "1abc", "1e", "1.", "1.2.3", "2e+", "5u9", "-1x"] {
+            assert!(type_literal(&types, literal, None).is_none(), "`{literal}` is a literal");
+        }
+    }
+
+    #[test]
+    fn expected_type_is_used_only_if_value_fits() {
+        assert_eq!(literal_type("255", Some(U8_TYPE)), U8_TYPE);
+        assert_eq!(literal_type("256", Some(U8_TYPE)), I64_TYPE);
+        assert_eq!(literal_type("-1", Some(U8_TYPE)), I64_TYPE);
+        assert_eq!(literal_type("5u16", Some(U8_TYPE)), U16_TYPE);
+    }
+
+    fn declaration(types: &HashMap<String, Rc<Type>>, field_types: &[&str]) -> Vec<(Rc<String>, Rc<Type>)> {
+        field_types.iter()
+            .enumerate()
+            .map(|(i, name)| (Rc::new(format!("f{i}")), types[*name].clone()))
+            .collect()
+    }
+
+    fn offsets(fields: &[Field]) -> Vec<usize> {
+        fields.iter().map(|field| field.offset).collect()
+    }
+
+    #[test]
+    fn struct_fields_are_aligned() {
+        let types = compile_native_types();
+        let point = Type::structure("P".to_string(), declaration(&types, &[U8_TYPE, I64_TYPE, U16_TYPE]));
+        assert_eq!(offsets(point.fields().unwrap()), vec![0, 8, 16]);
+        assert_eq!((point.size, point.alignment), (24, 8));
+
+        let packed = Type::structure("Q".to_string(), declaration(&types, &[U16_TYPE, U8_TYPE, U8_TYPE]));
+        assert_eq!(offsets(packed.fields().unwrap()), vec![0, 2, 3]);
+        assert_eq!((packed.size, packed.alignment), (4, 2));
+
+        let empty = Type::structure("E".to_string(), Vec::new());
+        assert_eq!((empty.size, empty.alignment), (0, 1));
+    }
+
+    #[test]
+    fn enum_payloads_follow_discriminant() {
+        let types = compile_native_types();
+        let variants = vec![
+            (Rc::new("A".to_string()), Vec::new()),
+            (Rc::new("B".to_string()), declaration(&types, &[U8_TYPE, I32_TYPE])),
+            (Rc::new("C".to_string()), declaration(&types, &[I64_TYPE])),
+        ];
+        let shape = Type::enumeration("S".to_string(), variants);
+        let variants = shape.variants().unwrap();
+        assert_eq!(offsets(&variants[1].fields), vec![8, 12]);
+        assert_eq!(offsets(&variants[2].fields), vec![8]);
+        assert_eq!((shape.size, shape.alignment), (16, 8));
+
+        let small = Type::enumeration("T".to_string(), vec![(Rc::new("A".to_string()), declaration(&types, &[U16_TYPE]))]);
+        assert_eq!(offsets(&small.variants().unwrap()[0].fields), vec![2]);
+        assert_eq!((small.size, small.alignment), (4, 2));
+    }
+
+    #[test]
+    fn payload_less_enum_is_its_discriminant() {
+        let variants = |count: usize| (0..count).map(|i| (Rc::new(format!("V{i}")), Vec::new())).collect();
+        let byte = Type::enumeration("B".to_string(), variants(256));
+        assert_eq!((byte.size, byte.alignment), (1, 1));
+        let word = Type::enumeration("W".to_string(), variants(257));
+        assert_eq!((word.size, word.alignment), (2, 2));
     }
 }
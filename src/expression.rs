@@ -1,26 +1,31 @@
 use phf::phf_map;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::env::var;
-use std::ops::Index;
 use std::rc::Rc;
-use std::string::ToString;
-use crate::ast_node::{ASTNode, BinaryOperator, Literal, Namespace};
-use crate::compiler_error::{raise_compiler_error, CompilerError};
-use crate::compiler_error::CompilerError::{BinaryOperatorTypeError, InvalidAssignment, InvalidExpression, InvalidSymbol, MismatchedParentheses};
-use crate::data_type::{get_literal_type, Type};
+use crate::ast_node::{adopt_literal_type, ASTNode, Assignment, BinaryOperator, Cast, FieldAccess, FunctionCall, FunctionRef, IndirectCall, Literal, LogicalOperator, Namespace, ShiftKind, ShiftOperator, SliceLiteral, StructLiteral, UnaryOperator, VariantLiteral};
+use crate::compiler_error::CompilerError;
+use crate::compiler_error::CompilerError::{AmbiguousCall, ArgumentCountError, ArgumentTypeError, AssignmentTypeError, BinaryOperatorTypeError, ImmutableAssignment, InvalidAssignment, InvalidCast, InvalidExpression, InvalidSymbol, LiteralOutOfRange, MismatchedParentheses, NoMatchingOverload, NotCallable, OverloadedFunctionValue, TypeAsValue, UnaryOperatorTypeError, UndefinedType, UnexpectedToken, UnknownArgument, UnknownField, UnknownVariant};
+use crate::conversion::{cast_kind, coerce, common_type, convert, widens_to};
+use crate::data_type::{join_types, parse_type, type_literal, Field, Type};
+use crate::operator_signature::{binary_result_type, unary_result_type};
+use crate::function_node::{ArgBindings, FunctionSignature};
 use crate::line::Line;
 
 const PAREN_OPEN: &str = "(";
 const PAREN_CLOSE: &str = ")";
+const ARG_DELIMITER: &str = ",";
+const NAMED_ARG_TOKEN: &str = "=";
+const FIELD_ACCESS_TOKEN: &str = ".";
 
-struct Expression<'a> {
-    tokens: &'a [String],
+pub struct Expression<'a> {
+    tokens: &'a [Rc<String>],
     token_index: usize,
+    operand_start: usize,
     line: &'a Line<'a>,
-    op_group_index: usize,
-    paren_matches: &'a [usize],
     types: &'a HashMap<String, Rc<Type>>,
-    namespace: Rc<Namespace<'a>>,
+    namespace: Rc<RefCell<Namespace>>,
+    // Type the surrounding context wants, used to type unsuffixed literals
+    expected: Option<Rc<Type>>,
 }
 
 impl<'a> Expression<'a> {
@@ -28,215 +33,766 @@ impl<'a> Expression<'a> {
            start: usize,
            end: usize,
            types: &'a HashMap<String, Rc<Type>>,
-           namespace: Rc<Namespace<'a>>
+           namespace: Rc<RefCell<Namespace>>,
+           expected: Option<Rc<Type>>
     ) -> Self {
         Self {
             tokens: &line.tokens[start..end],
             token_index: 0,
+            operand_start: 0,
             line,
-            op_group_index: 0,
-            paren_matches: &[],
             types,
             namespace,
+            expected,
         }
     }
 
-    fn sub_expression(&'a self, tokens: &'a [String], op_group_index: usize) -> Self {
-        Expression {
-            tokens,
-            token_index: 0,
-            line: self.line,
-            op_group_index,
-            paren_matches: self.paren_matches,
-            types: self.types,
-            namespace: self.namespace.clone(),
-        }
+    fn peek(&self) -> Option<&'a Rc<String>> {
+        self.tokens.get(self.token_index)
     }
 
-    fn len(&self) -> usize {
-        self.tokens.len()
+    fn next_token(&mut self) -> Result<&'a Rc<String>, CompilerError> {
+        let token = self.peek().ok_or(InvalidExpression(self.line.line_num))?;
+        self.token_index += 1;
+        Ok(token)
     }
 
-    fn last(&self) -> &String {
-        &self[self.len() - 1]
+    // Source text of the left operand of the operator that was just consumed
+    fn left_operand_text(&self) -> String {
+        self.tokens[self.operand_start..self.token_index - 1]
+            .iter()
+            .map(|token| token.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 }
 
-impl Index<usize> for Expression<'_> {
-    type Output = String;
+pub type ParseResult = Result<Box<dyn ASTNode>, CompilerError>;
+type PrefixParser = fn(&mut Expression, &Rc<String>, u8) -> ParseResult;
+type InfixParser = fn(&mut Expression, Box<dyn ASTNode>, &Rc<String>, u8) -> ParseResult;
+type PostfixParser = fn(&mut Expression, Box<dyn ASTNode>, &Rc<String>) -> ParseResult;
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.tokens[index]
-    }
+#[derive(Copy, Clone)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+struct PrefixOperator {
+    precedence: u8,
+    parser: PrefixParser,
+}
+
+struct InfixOperator {
+    precedence: u8,
+    associativity: Associativity,
+    parser: InfixParser,
+}
+
+struct PostfixOperator {
+    precedence: u8,
+    parser: PostfixParser,
 }
 
-const OPERATOR_GROUPS: usize = 2;
-static OPERATORS: [phf::Map<&'static str, fn(expression: &Expression) -> Result<Box<dyn ASTNode>, CompilerError>>; OPERATOR_GROUPS] = [
-    phf_map! {
-        "+" => add_parser,
-        "-" => sub_parser,
-    },
-    phf_map! {
-        "*" => mul_parser,
-        "/" => div_parser,
-        "%" => mod_parser,
+// Binding power of each operator level, loosest first
+const LOWEST_PRECEDENCE: u8 = 0;
+const ASSIGNMENT_PRECEDENCE: u8 = 1;
+const LOGICAL_OR_PRECEDENCE: u8 = 2;
+const LOGICAL_AND_PRECEDENCE: u8 = 3;
+const BITWISE_OR_PRECEDENCE: u8 = 4;
+const BITWISE_XOR_PRECEDENCE: u8 = 5;
+const BITWISE_AND_PRECEDENCE: u8 = 6;
+const EQUALITY_PRECEDENCE: u8 = 7;
+const COMPARISON_PRECEDENCE: u8 = 8;
+const SHIFT_PRECEDENCE: u8 = 9;
+const SUM_PRECEDENCE: u8 = 10;
+const PRODUCT_PRECEDENCE: u8 = 11;
+const CAST_PRECEDENCE: u8 = 12;
+const PREFIX_PRECEDENCE: u8 = 13;
+const CALL_PRECEDENCE: u8 = 14;
+
+static PREFIX_OPERATORS: phf::Map<&'static str, PrefixOperator> = phf_map! {
+    "(" => PrefixOperator { precedence: LOWEST_PRECEDENCE, parser: parse_parenthetical_expression },
+    "-" => PrefixOperator { precedence: PREFIX_PRECEDENCE, parser: unary_operation_parser },
+    "!" => PrefixOperator { precedence: PREFIX_PRECEDENCE, parser: unary_operation_parser },
+    "~" => PrefixOperator { precedence: PREFIX_PRECEDENCE, parser: unary_operation_parser },
+};
+
+static INFIX_OPERATORS: phf::Map<&'static str, InfixOperator> = phf_map! {
+    "=" => InfixOperator { precedence: ASSIGNMENT_PRECEDENCE, associativity: Associativity::Right, parser: assignment_parser },
+    "||" => InfixOperator { precedence: LOGICAL_OR_PRECEDENCE, associativity: Associativity::Left, parser: logical_operation_parser },
+    "&&" => InfixOperator { precedence: LOGICAL_AND_PRECEDENCE, associativity: Associativity::Left, parser: logical_operation_parser },
+    "|" => InfixOperator { precedence: BITWISE_OR_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "^" => InfixOperator { precedence: BITWISE_XOR_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "&" => InfixOperator { precedence: BITWISE_AND_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "==" => InfixOperator { precedence: EQUALITY_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "!=" => InfixOperator { precedence: EQUALITY_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "<" => InfixOperator { precedence: COMPARISON_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "<=" => InfixOperator { precedence: COMPARISON_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    ">" => InfixOperator { precedence: COMPARISON_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    ">=" => InfixOperator { precedence: COMPARISON_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "<<" => InfixOperator { precedence: SHIFT_PRECEDENCE, associativity: Associativity::Left, parser: shift_parser },
+    ">>" => InfixOperator { precedence: SHIFT_PRECEDENCE, associativity: Associativity::Left, parser: shift_parser },
+    "+" => InfixOperator { precedence: SUM_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "-" => InfixOperator { precedence: SUM_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "*" => InfixOperator { precedence: PRODUCT_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "/" => InfixOperator { precedence: PRODUCT_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "%" => InfixOperator { precedence: PRODUCT_PRECEDENCE, associativity: Associativity::Left, parser: binary_operation_parser },
+    "as" => InfixOperator { precedence: CAST_PRECEDENCE, associativity: Associativity::Left, parser: cast_parser },
+};
+
+static POSTFIX_OPERATORS: phf::Map<&'static str, PostfixOperator> = phf_map! {
+    "(" => PostfixOperator { precedence: CALL_PRECEDENCE, parser: indirect_call_parser },
+    "." => PostfixOperator { precedence: CALL_PRECEDENCE, parser: field_access_parser },
+};
+
+type Operands = (Box<dyn ASTNode>, Box<dyn ASTNode>, Rc<Type>);
+
+// Parses the right operand and returns both operands, converted to a common type if the
+// operator doesn't accept them as they are, along with the result type
+fn parse_right_operand(expression: &mut Expression,
+                       mut left: Box<dyn ASTNode>,
+                       operator: &Rc<String>,
+                       precedence: u8
+) -> Result<Operands, CompilerError> {
+    let mut right = parse_with_expected(expression, Some(left.get_type()), precedence)?;
+    adopt_literal_type(&mut right, &left.get_type());
+    adopt_literal_type(&mut left, &right.get_type());
+
+    let (left_type, right_type) = (left.get_type(), right.get_type());
+    if binary_result_type(operator, &left_type, &right_type, expression.types).is_none() {
+        if let Some(common) = common_type(&left_type, &right_type, expression.types) {
+            left = coerce(left, &common);
+            right = coerce(right, &common);
+        }
     }
-];
 
-fn get_operator_parser<'a>(
-    expression: &Expression
-) -> Option<fn(expression: &Expression) -> Result<Box<dyn ASTNode>, CompilerError>> {
-    if let Some(&parser) = OPERATORS[expression.op_group_index]
-        .get(&expression.tokens[expression.token_index]) {
-        return Some(parser)
+    match binary_result_type(operator, &left.get_type(), &right.get_type(), expression.types) {
+        Some(result_type) => Ok((left, right, result_type)),
+        None => Err(BinaryOperatorTypeError(
+            expression.line.line_num, operator.to_string(),
+            left_type.to_string(), right_type.to_string())
+        ),
     }
+}
 
-    None
+fn binary_operation_parser(expression: &mut Expression,
+                           left: Box<dyn ASTNode>,
+                           operator: &Rc<String>,
+                           precedence: u8
+) -> ParseResult {
+    let (left, right, result_type) = parse_right_operand(expression, left, operator, precedence)?;
+    Ok(Box::new(BinaryOperator::new(operator.clone(), result_type, left, right)))
 }
 
-fn binary_operation_parser<'a>(expression: &'a mut Expression) -> Result<Box<dyn ASTNode + 'a>, CompilerError> {
-    let op_index = expression.token_index;
-    let op_group_index = expression.op_group_index;
+fn logical_operation_parser(expression: &mut Expression,
+                            left: Box<dyn ASTNode>,
+                            operator: &Rc<String>,
+                            precedence: u8
+) -> ParseResult {
+    let (left, right, result_type) = parse_right_operand(expression, left, operator, precedence)?;
+    Ok(Box::new(LogicalOperator::new(operator.clone(), result_type, left, right)))
+}
 
-    expression.tokens = &expression.tokens[..op_index];
-    let mut sub_expr = expression.sub_expression(expression.tokens, op_group_index);
-    let left: Box<dyn ASTNode + 'a> = parse_expression(&mut sub_expr)?;
+fn shift_parser(expression: &mut Expression,
+                left: Box<dyn ASTNode>,
+                operator: &Rc<String>,
+                precedence: u8
+) -> ParseResult {
+    const LEFT_SHIFT: &str = "<<";
 
-    expression.op_group_index = op_group_index + 1;
-    expression.tokens = &expression.tokens[op_group_index+1..];
-    let right: Box<dyn ASTNode + 'a> = parse_expression(expression)?;
+    let (left, right, result_type) = parse_right_operand(expression, left, operator, precedence)?;
 
-    if left.get_type() != right.get_type() {
-        Err(BinaryOperatorTypeError(
-            expression.line.line_num, expression[op_index].to_string(),
-            left.get_type().to_string(), right.get_type().to_string())
-        )
+    let kind = if operator.as_str() == LEFT_SHIFT {
+        ShiftKind::Left
+    } else if left.get_type().is_signed_integer() {
+        ShiftKind::ArithmeticRight
     } else {
-        let x = Box::new(BinaryOperator::new(left, right));
-        Ok(x)
+        ShiftKind::LogicalRight
+    };
+
+    Ok(Box::new(ShiftOperator::new(kind, result_type, left, right)))
+}
+
+// The right side of `as` is a type rather than an operand
+fn cast_parser(expression: &mut Expression,
+               value: Box<dyn ASTNode>,
+               _: &Rc<String>,
+               _: u8
+) -> ParseResult {
+    let line_num = expression.line.line_num;
+    let type_name = expression.peek().ok_or(InvalidExpression(line_num))?;
+    let (target, type_end) = parse_type(expression.tokens, expression.token_index, expression.types)
+        .ok_or(UndefinedType(line_num, type_name.to_string()))?;
+    expression.token_index = type_end;
+
+    match cast_kind(&value.get_type(), &target) {
+        Some(kind) => Ok(Box::new(Cast::new(target, kind, value))),
+        None => Err(InvalidCast(line_num, value.get_type().to_string(), target.to_string())),
+    }
+}
+
+fn unary_operation_parser(expression: &mut Expression,
+                          operator: &Rc<String>,
+                          precedence: u8
+) -> ParseResult {
+    const NEGATION: &str = "-";
+
+    // Negative numbers are literals so that they are range checked as a whole, e.g. `-128i8`
+    let negated_literal = expression.peek()
+        .filter(|_| operator.as_str() == NEGATION)
+        .and_then(|token| literal_node(expression, &format!("{NEGATION}{token}")));
+    if let Some(literal) = negated_literal {
+        expression.token_index += 1;
+        return literal;
+    }
+    let operand = parse_expression(expression, precedence)?;
+    let operand_type = operand.get_type();
+
+    match unary_result_type(operator, &operand_type, expression.types) {
+        Some(result_type) => Ok(Box::new(UnaryOperator::new(operator.clone(), result_type, operand))),
+        None => Err(UnaryOperatorTypeError(
+            expression.line.line_num, operator.to_string(), operand_type.to_string())
+        ),
+    }
+}
+
+fn assignment_parser(expression: &mut Expression,
+                     left: Box<dyn ASTNode>,
+                     _: &Rc<String>,
+                     precedence: u8
+) -> ParseResult {
+    let target = expression.left_operand_text();
+    if !left.is_lvalue() {
+        return Err(InvalidAssignment(expression.line.line_num, target));
+    }
+    if !left.is_mutable() {
+        return Err(ImmutableAssignment(expression.line.line_num, target));
+    }
+
+    let right = parse_with_expected(expression, Some(left.get_type()), precedence)?;
+    let line_num = expression.line.line_num;
+    let right = convert(right, &left.get_type(), line_num)?;
+    if left.get_type() != right.get_type() {
+        return Err(AssignmentTypeError(line_num, target, left.get_type().to_string(), right.get_type().to_string()));
+    }
+
+    Ok(Box::new(Assignment::new(left, right)))
+}
+
+fn parse_parenthetical_expression(expression: &mut Expression, _: &Rc<String>, _: u8) -> ParseResult {
+    let inner = parse_expression(expression, LOWEST_PRECEDENCE)?;
+
+    match expression.peek() {
+        Some(token) if token.as_str() == PAREN_CLOSE => {
+            expression.token_index += 1;
+            Ok(inner)
+        }
+        _ => Err(MismatchedParentheses(expression.line.line_num)),
     }
 }
 
-fn add_parser(expression: &Expression) -> Result<Box<dyn ASTNode>, CompilerError> {
-    Err(InvalidExpression(expression.line.line_num))
+type CallArgs = Vec<(Option<Rc<String>>, Box<dyn ASTNode>)>;
+
+fn parse_call_arg_name(expression: &mut Expression) -> Option<Rc<String>> {
+    let name = expression.peek()?;
+    match expression.tokens.get(expression.token_index + 1) {
+        Some(token) if token.as_str() == NAMED_ARG_TOKEN => {
+            expression.token_index += 2;
+            Some(name.clone())
+        }
+        _ => None,
+    }
 }
 
-fn sub_parser(expression: &Expression) -> Result<Box<dyn ASTNode>, CompilerError> {
-    Err(InvalidExpression(expression.line.line_num))
+fn parse_call_args(expression: &mut Expression) -> Result<CallArgs, CompilerError> {
+    let mut args = Vec::new();
+    if expression.peek().is_some_and(|token| token.as_str() == PAREN_CLOSE) {
+        expression.token_index += 1;
+        return Ok(args);
+    }
+
+    loop {
+        let name = parse_call_arg_name(expression);
+        args.push((name, parse_with_expected(expression, None, LOWEST_PRECEDENCE)?));
+
+        match expression.peek().map(|token| token.as_str()) {
+            Some(ARG_DELIMITER) => expression.token_index += 1,
+            Some(PAREN_CLOSE) => {
+                expression.token_index += 1;
+                return Ok(args);
+            }
+            Some(_) => return Err(InvalidExpression(expression.line.line_num)),
+            None => return Err(MismatchedParentheses(expression.line.line_num)),
+        }
+    }
 }
 
-fn mul_parser(expression: &Expression) -> Result<Box<dyn ASTNode>, CompilerError> {
-    Err(InvalidExpression(expression.line.line_num))
+fn join_signatures(signatures: &[Rc<FunctionSignature>]) -> String {
+    signatures.iter().map(|signature| format!("`{signature}`")).collect::<Vec<_>>().join(", ")
 }
 
-fn div_parser(expression: &Expression) -> Result<Box<dyn ASTNode>, CompilerError> {
-    Err(InvalidExpression(expression.line.line_num))
+// Parameter type each argument is bound to, in argument order
+fn param_types_by_arg(function: &FunctionSignature, bindings: &ArgBindings) -> Vec<Rc<Type>> {
+    let mut bound_params = function.bound_params(bindings);
+    bound_params.sort_by_key(|(arg_index, _, _)| *arg_index);
+    bound_params.into_iter().map(|(_, _, param_type)| param_type).collect()
 }
 
-fn mod_parser(expression: &Expression) -> Result<Box<dyn ASTNode>, CompilerError> {
-    Err(InvalidExpression(expression.line.line_num))
+fn resolve_overload(line_num: usize,
+                    overloads: Vec<Rc<FunctionSignature>>,
+                    args: &CallArgs
+) -> Result<(Rc<FunctionSignature>, ArgBindings), CompilerError> {
+    let arg_names: Vec<Option<Rc<String>>> = args.iter().map(|(name, _)| name.clone()).collect();
+    let arg_nodes: Vec<&dyn ASTNode> = args.iter().map(|(_, arg)| arg.as_ref()).collect();
+
+    if let [function] = overloads.as_slice() {
+        let bindings = function.bind_args(&arg_names, line_num)?;
+        function.check_arg_types(&bindings, &arg_nodes, line_num)?;
+        return Ok((function.clone(), bindings));
+    }
+
+    let mut matches: Vec<(Rc<FunctionSignature>, ArgBindings)> = overloads.iter()
+        .filter_map(|overload| {
+            overload.accepts(&arg_names, &arg_nodes).map(|bindings| (overload.clone(), bindings))
+        })
+        .collect();
+    // Overloads that need a lossy conversion, then ones that need a literal retyped, then
+    // variadic overloads, are only chosen when nothing more specific fits
+    if matches.iter().any(|(function, bindings)| function.is_lossless_match(bindings, &arg_nodes)) {
+        matches.retain(|(function, bindings)| function.is_lossless_match(bindings, &arg_nodes));
+    }
+    if matches.iter().any(|(function, bindings)| function.is_exact_match(bindings, &arg_nodes)) {
+        matches.retain(|(function, bindings)| function.is_exact_match(bindings, &arg_nodes));
+    }
+    if matches.iter().any(|(function, _)| !function.variadic) {
+        matches.retain(|(function, _)| !function.variadic);
+    }
+    // Among widenings the narrowest wins, e.g. `p(i16)` over `p(i32)` for an `i8` argument.
+    // Candidates where neither is narrower for every argument stay ambiguous.
+    let param_types: Vec<Vec<Rc<Type>>> = matches.iter()
+        .map(|(function, bindings)| param_types_by_arg(function, bindings))
+        .collect();
+    let is_narrower = |narrow: &[Rc<Type>], wide: &[Rc<Type>]| {
+        narrow.iter().zip(wide).all(|(narrow, wide)| narrow == wide || widens_to(narrow, wide))
+    };
+    let narrowest: Vec<usize> = (0..matches.len())
+        .filter(|&i| param_types.iter().all(|other| is_narrower(&param_types[i], other)))
+        .collect();
+    if let [narrowest] = narrowest[..] {
+        matches = vec![matches.swap_remove(narrowest)];
+    }
+
+    let name = overloads[0].name.to_string();
+    match matches.len() {
+        1 => Ok(matches.remove(0)),
+        0 => {
+            let arg_types: Vec<Rc<Type>> = arg_nodes.iter().map(|arg| arg.get_type()).collect();
+            Err(NoMatchingOverload(line_num, name, join_types(&arg_types), join_signatures(&overloads)))
+        }
+        _ => {
+            let candidates: Vec<Rc<FunctionSignature>> = matches.into_iter().map(|(function, _)| function).collect();
+            Err(AmbiguousCall(line_num, name, join_signatures(&candidates)))
+        }
+    }
+}
+
+fn parse_function_call(expression: &mut Expression, overloads: Vec<Rc<FunctionSignature>>) -> ParseResult {
+    let args = parse_call_args(expression)?;
+    let (function, bindings) = resolve_overload(expression.line.line_num, overloads, &args)?;
+
+    let mut args: Vec<Option<Box<dyn ASTNode>>> = args.into_iter().map(|(_, arg)| Some(arg)).collect();
+    for (arg_index, _, param_type) in function.bound_params(&bindings) {
+        args[arg_index] = args[arg_index].take().map(|arg| convert(arg, &param_type, expression.line.line_num)).transpose()?;
+    }
+
+    let mut ordered_args: Vec<Option<Box<dyn ASTNode>>> = bindings.params.iter()
+        .map(|binding| binding.and_then(|arg_index| args[arg_index].take()))
+        .collect();
+
+    if function.variadic {
+        let elements = bindings.variadic.iter().filter_map(|&arg_index| args[arg_index].take()).collect();
+        let slice_type = function.param_types.last().unwrap().clone();
+        ordered_args.push(Some(Box::new(SliceLiteral::new(slice_type, elements))));
+    }
+
+    Ok(Box::new(FunctionCall::new(function, ordered_args)))
 }
 
-fn assignment_parser<'a>(expression: &'a mut Expression) -> Result<Box<dyn ASTNode + 'a>, CompilerError> {
-    if expression.token_index != 1 {
-        return Err(InvalidAssignment(expression.line.line_num));
+fn indirect_call_parser(expression: &mut Expression, callee: Box<dyn ASTNode>, _: &Rc<String>) -> ParseResult {
+    let line_num = expression.line.line_num;
+    let callee_text = expression.left_operand_text();
+    let callee_type = callee.get_type();
+    let (param_types, ret_type) = callee_type.function_signature()
+        .ok_or(NotCallable(line_num, callee_text.clone(), callee_type.to_string()))?;
+
+    let args = parse_call_args(expression)?;
+    if let Some((Some(name), _)) = args.iter().find(|(name, _)| name.is_some()) {
+        return Err(UnknownArgument(line_num, callee_text, name.to_string()));
+    }
+    if args.len() != param_types.len() {
+        return Err(ArgumentCountError(line_num, callee_text, param_types.len(), args.len()));
     }
 
-    let var_node_opt = expression.namespace.get_var(&expression.tokens[0]);
-    if var_node_opt.is_none() {
-        return Err(InvalidSymbol(expression.line.line_num, expression.tokens[0].to_string()));
+    let mut coerced_args = Vec::with_capacity(args.len());
+    for (i, ((_, arg), param_type)) in args.into_iter().zip(param_types).enumerate() {
+        let arg = convert(arg, param_type, line_num)?;
+        if arg.get_type() != *param_type {
+            return Err(ArgumentTypeError(
+                line_num, callee_text, (i + 1).to_string(),
+                param_type.to_string(), arg.get_type().to_string()
+            ));
+        }
+        coerced_args.push(arg);
     }
 
-    let var_node = Box::new(var_node_opt.unwrap().clone());
-    let mut assignment_expr = expression.sub_expression(
-        &expression.tokens[expression.token_index + 1..], 0);
+    Ok(Box::new(IndirectCall::new(ret_type, callee, coerced_args)))
+}
+
+fn field_access_parser(expression: &mut Expression, base: Box<dyn ASTNode>, _: &Rc<String>) -> ParseResult {
+    let line_num = expression.line.line_num;
+    let field_name = expression.next_token()?;
+    let base_type = base.get_type();
+    let field = base_type.field(field_name)
+        .ok_or(UnknownField(line_num, base_type.to_string(), field_name.to_string()))?;
 
-    Ok(Box::new(BinaryOperator::new(var_node, parse_expression(&mut assignment_expr)?)))
+    Ok(Box::new(FieldAccess::new(base, field)))
 }
 
-fn parse_parenthetical_expression<'a>(expression: &'a mut Expression) -> Result<Box<dyn ASTNode +'a>, CompilerError> {
-    expression.tokens = &expression.tokens[1..expression.tokens.len() - 1];
-    expression.op_group_index = 0;
-    parse_expression(expression)
+// Field values of a struct or variant literal in declaration order
+fn parse_constructor_args(expression: &mut Expression,
+                          constructor: FunctionSignature
+) -> Result<Vec<Box<dyn ASTNode>>, CompilerError> {
+    let args = parse_call_args(expression)?;
+    let (constructor, bindings) = resolve_overload(expression.line.line_num, vec![Rc::new(constructor)], &args)?;
+
+    let mut args: Vec<Option<Box<dyn ASTNode>>> = args.into_iter().map(|(_, arg)| Some(arg)).collect();
+    bindings.params.iter()
+        .zip(&constructor.param_types)
+        .filter_map(|(binding, field_type)| {
+            binding.and_then(|arg_index| args[arg_index].take()).map(|arg| convert(arg, field_type, expression.line.line_num))
+        })
+        .collect()
 }
 
-fn parse_value<'a>(expression: &'a Expression<'a>) -> Result<Box<dyn ASTNode + 'a>, CompilerError> {
-    let token = &expression.tokens[expression.token_index];
+fn parse_struct_literal(expression: &mut Expression, struct_type: &Rc<Type>, fields: &[Field]) -> ParseResult {
+    let constructor = FunctionSignature::constructor(struct_type.to_string(), fields, struct_type.clone());
+    let fields = parse_constructor_args(expression, constructor)?;
+    Ok(Box::new(StructLiteral::new(struct_type.clone(), fields)))
+}
 
-    if let Some(literal_type) = get_literal_type(expression.types, token) {
-        return Ok(Box::new(Literal::new(literal_type.clone(), token)));
+// `Enum.Variant`, followed by the payload in parentheses if the variant has one
+fn parse_variant_literal(expression: &mut Expression, enum_type: &Rc<Type>) -> ParseResult {
+    let line_num = expression.line.line_num;
+    let variant_name = expression.next_token()?;
+    let variant = enum_type.variant(variant_name)
+        .ok_or(UnknownVariant(line_num, enum_type.to_string(), variant_name.to_string()))?;
+
+    let has_args = expression.peek().is_some_and(|token| token.as_str() == PAREN_OPEN);
+    if !has_args && !variant.fields.is_empty() {
+        return Err(UnexpectedToken(line_num, PAREN_OPEN.to_string()));
     }
-    if let Some(var_node) = expression.namespace.get_var(token) {
+
+    let payload = if has_args {
+        expression.token_index += 1;
+        let name = format!("{enum_type}{FIELD_ACCESS_TOKEN}{variant_name}");
+        parse_constructor_args(expression, FunctionSignature::constructor(name, &variant.fields, enum_type.clone()))?
+    } else {
+        Vec::new()
+    };
+
+    Ok(Box::new(VariantLiteral::new(enum_type.clone(), variant.discriminant, payload)))
+}
+
+fn literal_node(expression: &Expression, literal: &str) -> Option<ParseResult> {
+    let typed = type_literal(expression.types, literal, expression.expected.as_ref())?;
+    if !typed.data_type.is_valid_literal(&typed.value) {
+        return Some(Err(LiteralOutOfRange(
+            expression.line.line_num, literal.to_string(), typed.data_type.to_string())
+        ));
+    }
+
+    Some(Ok(Box::new(Literal::new(typed.data_type, Rc::new(typed.value), typed.suffixed))))
+}
+
+fn parse_value(expression: &mut Expression, token: &Rc<String>) -> ParseResult {
+    if let Some(literal) = literal_node(expression, token) {
+        return literal;
+    }
+    if let Some(var_node) = Namespace::get_var(&expression.namespace, token) {
         return Ok(Box::new(var_node));
     }
 
+    if let Some(user_type) = expression.types.get(token.as_str()) {
+        let next_token = expression.peek().map(|token| token.as_str());
+        if let (Some(fields), Some(PAREN_OPEN)) = (user_type.fields(), next_token) {
+            expression.token_index += 1;
+            return parse_struct_literal(expression, user_type, fields);
+        }
+        if let (Some(_), Some(FIELD_ACCESS_TOKEN)) = (user_type.variants(), next_token) {
+            expression.token_index += 1;
+            return parse_variant_literal(expression, user_type);
+        }
+    }
+
+    let overloads = expression.namespace.borrow().get_overloads(token);
+    if let Some(overloads) = overloads {
+        if expression.peek().is_some_and(|token| token.as_str() == PAREN_OPEN) {
+            expression.token_index += 1;
+            return parse_function_call(expression, overloads);
+        }
+
+        return match overloads.as_slice() {
+            [function] => Ok(Box::new(FunctionRef::new(function.clone()))),
+            _ => Err(OverloadedFunctionValue(expression.line.line_num, token.to_string())),
+        };
+    }
+    if expression.types.contains_key(token.as_str()) {
+        return Err(TypeAsValue(expression.line.line_num, token.to_string()));
+    }
+
     Err(InvalidSymbol(expression.line.line_num, token.to_string()))
 }
 
-fn parse_expression<'a>(expression: &'a mut Expression<'a>) -> Result<Box<dyn ASTNode + 'a>, CompilerError> {
-    if expression.len() == 1 {
-        return parse_value(expression);
-    }
+fn parse_prefix(expression: &mut Expression) -> ParseResult {
+    let token = expression.next_token()?;
 
-    if expression[0] == PAREN_OPEN && expression.last() == PAREN_CLOSE {
-        return parse_parenthetical_expression(expression);
+    match PREFIX_OPERATORS.get(token.as_str()) {
+        Some(operator) => (operator.parser)(expression, token, operator.precedence),
+        None if token.as_str() == PAREN_CLOSE => Err(MismatchedParentheses(expression.line.line_num)),
+        None => parse_value(expression, token),
     }
+}
 
-    while expression.op_group_index < OPERATOR_GROUPS {
-        expression.token_index = expression.len() - 1;
-        loop {
-            let token = &expression[expression.token_index];
-            if token == PAREN_CLOSE {
-                expression.token_index = expression.paren_matches[expression.token_index]
-            } else {
-                if let Some(operator) = get_operator_parser(&expression) {
-                    // incorrect lifetime
-                    return operator(&expression);
-                }
-            }
+// Parses with a different expected type, restoring the outer one afterwards
+fn parse_with_expected(expression: &mut Expression,
+                       expected: Option<Rc<Type>>,
+                       min_precedence: u8
+) -> ParseResult {
+    let outer_expected = std::mem::replace(&mut expression.expected, expected);
+    let result = parse_expression(expression, min_precedence);
+    expression.expected = outer_expected;
+    result
+}
+
+fn parse_expression(expression: &mut Expression, min_precedence: u8) -> ParseResult {
+    let start = expression.token_index;
+    let mut left = parse_prefix(expression)?;
 
-            if expression.token_index == 0 {
+    while let Some(token) = expression.peek() {
+        if let Some(operator) = POSTFIX_OPERATORS.get(token.as_str()) {
+            if operator.precedence < min_precedence {
                 break;
             }
-            expression.token_index -= 1;
+            expression.token_index += 1;
+            expression.operand_start = start;
+            left = (operator.parser)(expression, left, token)?;
+            continue;
         }
 
-        expression.op_group_index += 1;
+        let operator = match INFIX_OPERATORS.get(token.as_str()) {
+            Some(operator) if operator.precedence >= min_precedence => operator,
+            _ => break,
+        };
+        expression.token_index += 1;
+        expression.operand_start = start;
+
+        let right_precedence = match operator.associativity {
+            Associativity::Left => operator.precedence + 1,
+            Associativity::Right => operator.precedence,
+        };
+        left = (operator.parser)(expression, left, token, right_precedence)?;
     }
 
-    // TODO: handle errors
-    Err(InvalidExpression(expression.line.line_num))
+    Ok(left)
 }
 
-fn match_parens(expression: &Expression) -> Vec<usize> {
-    let paren_matches = vec![0; expression.len()];
-    let mut open_parens = Vec::new();
+pub fn expression_node(line: &Line,
+                       start: usize,
+                       end: usize,
+                       types: &HashMap<String, Rc<Type>>,
+                       namespace: Rc<RefCell<Namespace>>
+) -> ParseResult {
+    parse_full_expression(Expression::new(line, start, end, types, namespace, None))
+}
 
-    for (i, token) in expression.tokens.iter().enumerate() {
-        if token == PAREN_OPEN {
-            open_parens.push(i);
-        } else if token == PAREN_CLOSE {
-            if open_parens.len() == 0 {
-                raise_compiler_error(MismatchedParentheses(expression.line.line_num));
-            }
-            paren_matches[i] = open_parens.pop().unwrap();
+// Expression converted to `expected` where the coercion rules allow it
+pub fn typed_expression_node(line: &Line,
+                             start: usize,
+                             end: usize,
+                             types: &HashMap<String, Rc<Type>>,
+                             namespace: Rc<RefCell<Namespace>>,
+                             expected: &Rc<Type>
+) -> ParseResult {
+    let node = parse_full_expression(Expression::new(line, start, end, types, namespace, Some(expected.clone())))?;
+    Ok(coerce(node, expected))
+}
+
+fn parse_full_expression(mut expression: Expression) -> ParseResult {
+    let line = expression.line;
+    let node = parse_expression(&mut expression, LOWEST_PRECEDENCE)?;
+
+    match expression.peek() {
+        None => Ok(node),
+        Some(token) if token.as_str() == PAREN_CLOSE => Err(MismatchedParentheses(line.line_num)),
+        Some(_) => Err(InvalidExpression(line.line_num)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_node::{Mutability, VarNode};
+    use crate::data_type::{compile_native_types, BOOL_TYPE, I16_TYPE, I32_TYPE, I64_TYPE, I8_TYPE, STR_TYPE, VOID_TYPE};
+    use crate::line::with_lines;
+
+    // `code` parsed with mutable i64 variables `a` to `d`, a bool `f` and a struct `p` with an
+    // i64 field `b` in scope
+    fn parsed(code: &str) -> Result<String, CompilerError> {
+        let mut types = compile_native_types();
+        let fields = vec![(Rc::new("b".to_string()), types[I64_TYPE].clone())];
+        types.insert("P".to_string(), Rc::new(Type::structure("P".to_string(), fields)));
+
+        let namespace = Rc::new(RefCell::new(Namespace::new()));
+        let vars = [("a", I64_TYPE), ("b", I64_TYPE), ("c", I64_TYPE), ("d", I64_TYPE), ("f", BOOL_TYPE), ("p", "P")];
+        for (name, type_name) in vars {
+            let var = VarNode::new(types[type_name].clone(), Rc::new(name.to_string()), Mutability::Mutable);
+            namespace.borrow_mut().add_var(var);
         }
+
+        with_lines(code, |lines| {
+            expression_node(&lines[0], 0, lines[0].len(), &types, namespace).map(|node| node.dump())
+        })
     }
 
-    if open_parens.len() != 0 {
-        raise_compiler_error(MismatchedParentheses(expression.line.line_num));
+    #[test]
+    fn binary_operators_follow_precedence() {
+        assert_eq!(parsed("a + b * c").unwrap(), "(+ a (* b c))");
+        assert_eq!(parsed("a * b + c").unwrap(), "(+ (* a b) c)");
+        assert_eq!(parsed("a + b << c & d").unwrap(), "(& (Left (+ a b) c) d)");
+        assert_eq!(parsed("a < b == f || a == b && f").unwrap(), "(|| (== (< a b) f) (&& (== a b) f))");
     }
 
-    paren_matches
-}
+    #[test]
+    fn operators_associate() {
+        assert_eq!(parsed("a - b - c").unwrap(), "(- (- a b) c)");
+        assert_eq!(parsed("a / b * c").unwrap(), "(* (/ a b) c)");
+        assert_eq!(parsed("a = b = c").unwrap(), "(= a (= b c))");
+    }
+
+    #[test]
+    fn prefix_and_postfix_operators_bind() {
+        assert_eq!(parsed("-p.b").unwrap(), "(- (. p b@0))");
+        assert_eq!(parsed("-a * b").unwrap(), "(* (- a) b)");
+        assert_eq!(parsed("!f && f").unwrap(), "(&& (! f) f)");
+        assert_eq!(parsed("a as i32 + -b as i32").unwrap(), "(+ (Truncate i32 a) (Truncate i32 (- b)))");
+    }
+
+    #[test]
+    fn disallowed_casts_are_errors() {
+        assert!(matches!(parsed("\"x\" as i64"), Err(InvalidCast(..))));
+        assert!(matches!(parsed("p as i64"), Err(InvalidCast(..))));
+        assert!(matches!(parsed("a as bool"), Err(InvalidCast(..))));
+        assert_eq!(parsed("f as u8").unwrap(), "(Reinterpret u8 f)");
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(parsed("(a + b) * c").unwrap(), "(* (+ a b) c)");
+        assert_eq!(parsed("a - (b - c)").unwrap(), "(- a (- b c))");
+        assert_eq!(parsed("((a))").unwrap(), "a");
+    }
+
+    #[test]
+    fn mismatched_or_empty_parentheses_are_errors() {
+        for code in ["(a", "a)", "()", "(a + b))", "((a + b)", "a + ()"] {
+            assert!(parsed(code).is_err(), "`{code}` parsed");
+        }
+    }
+
+    fn overload(types: &HashMap<String, Rc<Type>>, param_types: &[&str]) -> Rc<FunctionSignature> {
+        Rc::new(FunctionSignature {
+            name: Rc::new("p".to_string()),
+            param_names: (0..param_types.len()).map(|i| Rc::new(format!("a{i}"))).collect(),
+            param_types: param_types.iter().map(|name| types[*name].clone()).collect(),
+            required_count: param_types.len(),
+            variadic: false,
+            ret_type: types[VOID_TYPE].clone(),
+        })
+    }
+
+    fn var_args(types: &HashMap<String, Rc<Type>>, arg_types: &[&str]) -> CallArgs {
+        arg_types.iter()
+            .map(|name| {
+                let var = VarNode::new(types[*name].clone(), Rc::new("x".to_string()), Mutability::Mutable);
+                (None, Box::new(var) as Box<dyn ASTNode>)
+            })
+            .collect()
+    }
 
-fn expression_node(line: &Line,
-                   start: usize,
-                   end: usize,
-                   types: &HashMap<String, Rc<Type>>,
-                   namespace: Rc<Namespace>
-) -> Result<Box<dyn ASTNode>, CompilerError> {
-    let mut expression = Expression::new(line, start, end, types, namespace);
-    expression.paren_matches = &match_parens(&expression);
-    parse_expression(&mut expression)
+    fn resolved(overloads: Vec<Rc<FunctionSignature>>, args: &CallArgs) -> Result<String, CompilerError> {
+        resolve_overload(1, overloads, args).map(|(function, _)| function.to_string())
+    }
+
+    #[test]
+    fn exact_match_beats_widening() {
+        let types = compile_native_types();
+        let overloads = vec![overload(&types, &[I64_TYPE]), overload(&types, &[I32_TYPE])];
+        let resolved = resolved(overloads, &var_args(&types, &[I32_TYPE])).unwrap();
+        assert_eq!(resolved, "p(i32) -> void");
+    }
+
+    #[test]
+    fn exact_match_beats_literal_retyping() {
+        let types = compile_native_types();
+        let literal = Literal::new(types[I64_TYPE].clone(), Rc::new("5".to_string()), false);
+        let args: CallArgs = vec![(None, Box::new(literal))];
+        let overloads = vec![overload(&types, &[I8_TYPE]), overload(&types, &[I64_TYPE])];
+        assert_eq!(resolved(overloads, &args).unwrap(), "p(i64) -> void");
+    }
+
+    #[test]
+    fn narrowest_widening_wins() {
+        let types = compile_native_types();
+        let overloads = vec![overload(&types, &[I32_TYPE]), overload(&types, &[I16_TYPE])];
+        let resolved = resolved(overloads, &var_args(&types, &[I8_TYPE])).unwrap();
+        assert_eq!(resolved, "p(i16) -> void");
+    }
+
+    #[test]
+    fn crossed_widenings_are_ambiguous() {
+        let types = compile_native_types();
+        let overloads = vec![overload(&types, &[I16_TYPE, I64_TYPE]), overload(&types, &[I64_TYPE, I16_TYPE])];
+        let result = resolved(overloads, &var_args(&types, &[I8_TYPE, I8_TYPE]));
+        assert!(matches!(result, Err(AmbiguousCall(..))));
+    }
+
+    #[test]
+    fn no_overload_accepts_arguments() {
+        let types = compile_native_types();
+        let overloads = vec![overload(&types, &[I32_TYPE]), overload(&types, &[I64_TYPE])];
+        let result = resolved(overloads, &var_args(&types, &[STR_TYPE]));
+        assert!(matches!(result, Err(NoMatchingOverload(..))));
+
+        let overloads = vec![overload(&types, &[I32_TYPE]), overload(&types, &[I64_TYPE])];
+        let result = resolved(overloads, &var_args(&types, &[I32_TYPE, I32_TYPE]));
+        assert!(matches!(result, Err(NoMatchingOverload(..))));
+    }
+
+    #[test]
+    fn widening_beats_lossy_conversion() {
+        let types = compile_native_types();
+        let overloads = vec![overload(&types, &[I8_TYPE]), overload(&types, &[I64_TYPE])];
+        let resolved = resolved(overloads, &var_args(&types, &[I32_TYPE])).unwrap();
+        assert_eq!(resolved, "p(i64) -> void");
+    }
+
+    #[test]
+    fn lossy_conversion_is_a_last_resort() {
+        // The argument is then converted with a warning, as it would be when assigned to an `i8`
+        let types = compile_native_types();
+        let overloads = vec![overload(&types, &[I8_TYPE]), overload(&types, &[STR_TYPE])];
+        let resolved = resolved(overloads, &var_args(&types, &[I64_TYPE])).unwrap();
+        assert_eq!(resolved, "p(i8) -> void");
+    }
 }
@@ -1,40 +1,249 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::rc::Rc;
 use regex::Regex;
-use crate::ast_node::{ASTNode, Namespace, VarNode};
+use crate::ast_node::{dump_block, ASTNode, Mutability, Namespace, VarNode};
 use crate::compiler_error::{raise_compiler_error, CompilerError};
-use crate::compiler_error::CompilerError::UndefinedType;
-use crate::data_type::Type;
+use crate::compiler_error::CompilerError::{ArgumentCountError, ArgumentTypeError, AssignmentTypeError, DuplicateArgument, InvalidVariadic, MissingArgument, NonConstantExpression, PositionalAfterNamed, RequiredAfterDefault, UndefinedType, UnknownArgument};
+use crate::conversion::{can_coerce, can_convert, convert};
+use crate::expression::typed_expression_node;
+use crate::data_type::{join_types, parse_type, Field, Type};
 use crate::line::Line;
 
+#[derive(Debug)]
+pub struct FunctionSignature {
+    pub name: Rc<String>,
+    pub param_names: Vec<Rc<String>>,
+    pub param_types: Vec<Rc<Type>>,
+    pub required_count: usize,
+    pub variadic: bool,
+    pub ret_type: Rc<Type>,
+}
+
+// Argument indices bound to each fixed parameter (None where the default is used) and the
+// arguments collected by a variadic parameter
+pub struct ArgBindings {
+    pub params: Vec<Option<usize>>,
+    pub variadic: Vec<usize>,
+}
+
+// Why arguments can't be bound to the parameters of a signature, with parameters given by index
+#[derive(Debug, PartialEq)]
+enum BindError {
+    PositionalAfterNamed,
+    TooManyArguments,
+    UnknownArgument(Rc<String>),
+    DuplicateArgument(usize),
+    MissingArgument(usize),
+}
+
+impl FunctionSignature {
+    // Struct and enum variant literals are checked like a call with one required parameter
+    // per field
+    pub fn constructor(name: String, fields: &[Field], ret_type: Rc<Type>) -> Self {
+        Self {
+            name: Rc::new(name),
+            param_names: fields.iter().map(|field| field.name.clone()).collect(),
+            param_types: fields.iter().map(|field| field.data_type.clone()).collect(),
+            required_count: fields.len(),
+            variadic: false,
+            ret_type,
+        }
+    }
+
+    pub fn fixed_count(&self) -> usize {
+        self.param_types.len() - self.variadic as usize
+    }
+
+    // Positional arguments bind in order, then named ones by name. Defaulted parameters may
+    // be left unbound.
+    fn try_bind_args(&self, arg_names: &[Option<Rc<String>>]) -> Result<ArgBindings, BindError> {
+        let fixed_count = self.fixed_count();
+        let mut bindings = vec![None; fixed_count];
+        let mut variadic = Vec::new();
+        let mut named_seen = false;
+
+        for (i, arg_name) in arg_names.iter().enumerate() {
+            let param_index = match arg_name {
+                None if named_seen => return Err(BindError::PositionalAfterNamed),
+                None if i >= fixed_count && self.variadic => {
+                    variadic.push(i);
+                    continue;
+                }
+                None if i >= fixed_count => return Err(BindError::TooManyArguments),
+                None => i,
+                Some(arg_name) => {
+                    named_seen = true;
+                    self.param_names[..fixed_count].iter()
+                        .position(|param_name| param_name == arg_name)
+                        .ok_or(BindError::UnknownArgument(arg_name.clone()))?
+                }
+            };
+
+            if bindings[param_index].is_some() {
+                return Err(BindError::DuplicateArgument(param_index));
+            }
+            bindings[param_index] = Some(i);
+        }
+
+        if let Some(missing) = bindings[..self.required_count].iter().position(Option::is_none) {
+            return Err(BindError::MissingArgument(missing));
+        }
+
+        Ok(ArgBindings { params: bindings, variadic })
+    }
+
+    pub fn bind_args(&self,
+                     arg_names: &[Option<Rc<String>>],
+                     line_num: usize
+    ) -> Result<ArgBindings, CompilerError> {
+        let name = self.name.to_string();
+        self.try_bind_args(arg_names).map_err(|error| match error {
+            BindError::PositionalAfterNamed => PositionalAfterNamed(line_num, name),
+            BindError::TooManyArguments => ArgumentCountError(line_num, name, self.fixed_count(), arg_names.len()),
+            BindError::UnknownArgument(arg_name) => UnknownArgument(line_num, name, arg_name.to_string()),
+            BindError::DuplicateArgument(param_index) => {
+                DuplicateArgument(line_num, name, self.param_names[param_index].to_string())
+            }
+            BindError::MissingArgument(param_index) => {
+                MissingArgument(line_num, name, self.param_names[param_index].to_string())
+            }
+        })
+    }
+
+    // Index of each passed argument with the name and type of the parameter it is bound to
+    pub fn bound_params(&self, bindings: &ArgBindings) -> Vec<(usize, Rc<String>, Rc<Type>)> {
+        let mut bound_params: Vec<(usize, Rc<String>, Rc<Type>)> = bindings.params.iter()
+            .enumerate()
+            .filter_map(|(param_index, binding)| {
+                binding.map(|arg_index| {
+                    (arg_index, self.param_names[param_index].clone(), self.param_types[param_index].clone())
+                })
+            })
+            .collect();
+
+        if let Some(element_type) = self.variadic_type() {
+            let param_name = &self.param_names[self.fixed_count()];
+            for &arg_index in &bindings.variadic {
+                bound_params.push((arg_index, param_name.clone(), element_type.clone()));
+            }
+        }
+
+        bound_params
+    }
+
+    // First argument that can't be converted to the type of its parameter
+    fn mismatched_arg(&self,
+                      bindings: &ArgBindings,
+                      args: &[&dyn ASTNode]
+    ) -> Option<(usize, Rc<String>, Rc<Type>)> {
+        self.bound_params(bindings)
+            .into_iter()
+            .find(|(arg_index, _, param_type)| !can_convert(args[*arg_index], param_type))
+    }
+
+    pub fn check_arg_types(&self,
+                           bindings: &ArgBindings,
+                           args: &[&dyn ASTNode],
+                           line_num: usize
+    ) -> Result<(), CompilerError> {
+        let Some((arg_index, param_name, param_type)) = self.mismatched_arg(bindings, args) else {
+            return Ok(());
+        };
+
+        let arg_type = args[arg_index].get_type();
+        Err(ArgumentTypeError(
+            line_num, self.name.to_string(), param_name.to_string(),
+            param_type.to_string(), arg_type.to_string()
+        ))
+    }
+
+    // Whether every argument fits its parameter without a lossy conversion
+    pub fn is_lossless_match(&self, bindings: &ArgBindings, args: &[&dyn ASTNode]) -> bool {
+        self.bound_params(bindings)
+            .iter()
+            .all(|(arg_index, _, param_type)| can_coerce(args[*arg_index], param_type))
+    }
+
+    // Whether every argument matches its parameter without being converted
+    pub fn is_exact_match(&self, bindings: &ArgBindings, args: &[&dyn ASTNode]) -> bool {
+        self.bound_params(bindings)
+            .iter()
+            .all(|(arg_index, _, param_type)| args[*arg_index].get_type() == *param_type)
+    }
+
+    pub fn variadic_type(&self) -> Option<Rc<Type>> {
+        match self.variadic {
+            true => self.param_types.last()?.element_type(),
+            false => None,
+        }
+    }
+
+    pub fn accepts(&self, arg_names: &[Option<Rc<String>>], args: &[&dyn ASTNode]) -> Option<ArgBindings> {
+        let bindings = self.try_bind_args(arg_names).ok()?;
+        self.mismatched_arg(&bindings, args).is_none().then_some(bindings)
+    }
+}
+
+impl Display for FunctionSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params = match self.variadic_type() {
+            Some(element_type) => {
+                let fixed = join_types(&self.param_types[..self.fixed_count()]);
+                if fixed.is_empty() {
+                    format!("{element_type}...")
+                } else {
+                    format!("{fixed}, {element_type}...")
+                }
+            }
+            None => join_types(&self.param_types),
+        };
+        write!(f, "{}({}) -> {}", self.name, params, self.ret_type)
+    }
+}
+
 pub struct Function {
     ret_type: Rc<Type>,
     name: Rc<String>,
     pub param_count: usize,
+    param_names: Vec<Rc<String>>,
+    param_types: Vec<Rc<Type>>,
+    default_ranges: Vec<(usize, usize)>,
+    defaults: Vec<Box<dyn ASTNode>>,
+    variadic: bool,
     pub namespace: Rc<RefCell<Namespace>>,
+    // Enclosing locals used by a nested function. Mutable ones are shared with the enclosing
+    // function, the rest are copied into the closure when it is defined.
+    captures: Vec<VarNode>,
     body: Vec<Box<dyn ASTNode>>
 }
 
+const PAREN_OPEN_TOKEN: &str = "(";
 const PAREN_CLOSE_TOKEN: &str = ")";
 const PARAM_DELIMITER: &str = ",";
+const DEFAULT_VALUE_TOKEN: &str = "=";
+const VARIADIC_TOKEN: &str = "...";
 
-fn assert_unique_var(var_name: Rc<String>, line: &Line, namespace: Rc<RefCell<Namespace>>) {
+pub fn assert_unique_var(var_name: Rc<String>, line: &Line, namespace: Rc<RefCell<Namespace>>) {
     if namespace.borrow().contains_var(var_name.clone()) {
         raise_compiler_error(CompilerError::SymbolAlreadyDefined(line.line_num, var_name.to_string()))
     }
 }
 
-fn assert_valid_symbol(symbol: Rc<String>, line: &Line) {
+pub fn is_valid_symbol(symbol: &str) -> bool {
     let valid_symbol_regex: Regex = Regex::new("^\\w+$").unwrap();
+    valid_symbol_regex.is_match(symbol)
+}
 
-    if !valid_symbol_regex.is_match(&symbol) {
+pub fn assert_valid_symbol(symbol: Rc<String>, line: &Line) {
+    if !is_valid_symbol(&symbol) {
         raise_compiler_error(CompilerError::InvalidSymbol(line.line_num, symbol.to_string()));
     }
 }
 
-fn assert_has_min_tokens(min_tokens: usize, line: &Line) {
-    if line.end - line.start < min_tokens {
+pub fn assert_has_min_tokens(min_tokens: usize, line: &Line) {
+    if line.len() < min_tokens {
         raise_compiler_error(CompilerError::InvalidDefinition(line.line_num));
     }
 }
@@ -52,7 +261,27 @@ fn assert_correct_delimiter(i: usize, line: &Line) {
     }
 }
 
-fn assert_valid_type(type_name: Rc<String>, type_opt: Option<&Rc<Type>>, line: &Line) {
+// Index of the `,` or `)` that ends a default value starting at `start`
+fn find_default_end(start: usize, line: &Line) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < line.len() - 1 {
+        match line[i].as_str() {
+            PAREN_OPEN_TOKEN => depth += 1,
+            PAREN_CLOSE_TOKEN => depth -= 1,
+            PARAM_DELIMITER if depth == 0 => break,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if i == start {
+        raise_compiler_error(CompilerError::InvalidDefinition(line.line_num));
+    }
+    i
+}
+
+fn assert_valid_type(type_name: Rc<String>, type_opt: Option<&(Rc<Type>, usize)>, line: &Line) {
     if type_opt.is_none() {
         raise_compiler_error(UndefinedType(line.line_num, type_name.to_string()));
     }
@@ -62,6 +291,21 @@ impl ASTNode for Function {
     fn get_type(&self) -> Rc<Type> {
         self.ret_type.clone()
     }
+
+    fn dump(&self) -> String {
+        let params: Vec<&str> = self.param_names.iter().map(|name| name.as_str()).collect();
+        let captures: Vec<String> = self.captures.iter()
+            .map(|var| match var.is_mutable() {
+                true => format!("mut {}", var.name()),
+                false => var.name().to_string(),
+            })
+            .collect();
+        let captures = match captures.is_empty() {
+            true => String::new(),
+            false => format!(" (captures {})", captures.join(" ")),
+        };
+        format!("(fn {} ({}){} {})", self.name, params.join(" "), captures, dump_block(&self.body))
+    }
 }
 
 impl Function {
@@ -70,12 +314,20 @@ impl Function {
             ret_type,
             name,
             param_count: 0,
-            namespace: Rc::new(RefCell::new(Namespace::new())),
+            param_names: Vec::new(),
+            param_types: Vec::new(),
+            default_ranges: Vec::new(),
+            defaults: Vec::new(),
+            variadic: false,
+            namespace: Rc::new(RefCell::new(Namespace::for_function())),
+            captures: Vec::new(),
             body: Vec::new(),
         }
     }
 
     fn add_param(&mut self, var: VarNode) {
+        self.param_names.push(var.name());
+        self.param_types.push(var.get_type());
         self.add_var(var);
         self.param_count += 1;
     }
@@ -85,46 +337,69 @@ impl Function {
     }
 
     fn parse_func_params(&mut self,
+                         param_start: usize,
                          line: &Line,
                          types: &HashMap<String, Rc<Type>>
     ) {
-        const PARAM_START_INDEX: usize = 3;
-
-        if line[PARAM_START_INDEX].as_str() != PAREN_CLOSE_TOKEN {
+        if line[param_start].as_str() == PAREN_CLOSE_TOKEN {
             return;
         }
 
-        let mut i = PARAM_START_INDEX;
+        let mut i = param_start;
         while i < line.len() {
             const MIN_TOKENS_REMAINING: usize = 3;
             assert_has_min_tokens(i + MIN_TOKENS_REMAINING, line);
 
-            let param_type_name = line[i].clone();
-            let param_type_opt = types.get(param_type_name.as_str());
-            assert_valid_type(param_type_name, param_type_opt, line);
-            let param_type = param_type_opt.unwrap();
+            let param_type_opt = parse_type(line.tokens, i, types);
+            assert_valid_type(line[i].clone(), param_type_opt.as_ref(), line);
+            let (mut param_type, type_end) = param_type_opt.unwrap();
+
+            i = type_end;
+            assert_has_min_tokens(i + 2, line);
+            let variadic = line[i].as_str() == VARIADIC_TOKEN;
+            if variadic {
+                param_type = Rc::new(Type::slice(param_type));
+                i += 1;
+                assert_has_min_tokens(i + 2, line);
+            }
 
-            i += 1;
             let param_name = line[i].clone();
             assert_valid_symbol(param_name.clone(), line);
 
             i += 1;
+            if variadic && (line[i].as_str() != PAREN_CLOSE_TOKEN || i + 1 != line.len()) {
+                raise_compiler_error(InvalidVariadic(line.line_num, param_name.to_string()));
+            }
+            self.variadic = variadic;
+
+            if line[i].as_str() == DEFAULT_VALUE_TOKEN {
+                let default_end = find_default_end(i + 1, line);
+                self.default_ranges.push((i + 1, default_end));
+                i = default_end;
+            } else if !variadic && !self.default_ranges.is_empty() {
+                raise_compiler_error(RequiredAfterDefault(line.line_num, param_name.to_string()));
+            }
             assert_correct_delimiter(i, line);
 
-            assert_unique_var(param_name.clone(), &line, self.namespace.clone());
-            self.add_param(VarNode::new(param_type.clone(), param_name));
+            assert_unique_var(param_name.clone(), line, self.namespace.clone());
+            self.add_param(VarNode::new(param_type, param_name, Mutability::Mutable));
             i += 1;
         }
     }
 
     fn create_func_def_node(ret_type: Rc<Type>,
+                            name_index: usize,
                             line: &Line,
                             types: &HashMap<String, Rc<Type>>
     ) -> Self {
-        const NAME_INDEX: usize = 1;
+        const MIN_NAME_TOKENS: usize = 3;
+        assert_has_min_tokens(name_index + MIN_NAME_TOKENS, line);
+
+        let name = line[name_index].clone();
+        assert_valid_symbol(name.clone(), line);
 
-        let mut func_node = Self::new(ret_type, line[NAME_INDEX].clone());
-        func_node.parse_func_params(line, types);
+        let mut func_node = Self::new(ret_type, name);
+        func_node.parse_func_params(name_index + 2, line, types);
 
         func_node
     }
@@ -135,12 +410,175 @@ impl Function {
         const MIN_DEF_TOKENS: usize = 4;
         assert_has_min_tokens(MIN_DEF_TOKENS, line);
 
-        match types.get(line[0].as_ref()).cloned() {
-            None => { None }
-            Some(ret_type) => {
-                Some(Self::create_func_def_node(ret_type, line, types))
-                // parse body
+        parse_type(line.tokens, 0, types).map(|(ret_type, name_index)| {
+            Self::create_func_def_node(ret_type, name_index, line, types)
+        })
+    }
+
+    pub fn name(&self) -> Rc<String> {
+        self.name.clone()
+    }
+
+    pub fn ret_type(&self) -> Rc<Type> {
+        self.ret_type.clone()
+    }
+
+    pub fn signature(&self) -> Rc<FunctionSignature> {
+        Rc::new(FunctionSignature {
+            name: self.name.clone(),
+            param_names: self.param_names.clone(),
+            param_types: self.param_types.clone(),
+            required_count: self.param_count - self.default_ranges.len() - self.variadic as usize,
+            variadic: self.variadic,
+            ret_type: self.ret_type.clone(),
+        })
+    }
+
+    // Defaults are parsed in the namespace enclosing the definition, so they can't see the params
+    pub fn parse_defaults(&mut self,
+                          line: &Line,
+                          types: &HashMap<String, Rc<Type>>,
+                          namespace: &Rc<RefCell<Namespace>>
+    ) -> Result<(), CompilerError> {
+        let first_default = self.param_count - self.default_ranges.len() - self.variadic as usize;
+
+        for (i, &(start, end)) in self.default_ranges.iter().enumerate() {
+            let param_name = self.param_names[first_default + i].to_string();
+            let param_type = &self.param_types[first_default + i];
+
+            let default = typed_expression_node(line, start, end, types, namespace.clone(), param_type)?;
+            let default = convert(default, param_type, line.line_num)?;
+            if default.get_type() != *param_type {
+                return Err(
+                    AssignmentTypeError(line.line_num, param_name, param_type.to_string(), default.get_type().to_string())
+                );
             }
+            if !default.is_constant() {
+                return Err(NonConstantExpression(line.line_num, param_name));
+            }
+
+            self.defaults.push(default);
         }
+
+        Ok(())
+    }
+
+    pub fn set_body(&mut self, body: Vec<Box<dyn ASTNode>>) {
+        self.body = body;
+        self.captures = self.namespace.borrow().captures();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_type::{compile_native_types, I64_TYPE, STR_TYPE, VOID_TYPE};
+    use crate::line::with_lines;
+
+    // `void range(i64 start, i64 stop, i64 step = 1)`
+    fn range_signature(types: &HashMap<String, Rc<Type>>) -> FunctionSignature {
+        FunctionSignature {
+            name: Rc::new("range".to_string()),
+            param_names: ["start", "stop", "step"].iter().map(|name| Rc::new(name.to_string())).collect(),
+            param_types: vec![types[I64_TYPE].clone(); 3],
+            required_count: 2,
+            variadic: false,
+            ret_type: types[VOID_TYPE].clone(),
+        }
+    }
+
+    fn names(arg_names: &[Option<&str>]) -> Vec<Option<Rc<String>>> {
+        arg_names.iter().map(|name| name.map(|name| Rc::new(name.to_string()))).collect()
+    }
+
+    fn var(types: &HashMap<String, Rc<Type>>, type_name: &str) -> VarNode {
+        VarNode::new(types[type_name].clone(), Rc::new("x".to_string()), Mutability::Mutable)
+    }
+
+    #[test]
+    fn positional_then_named_arguments_bind() {
+        let signature = range_signature(&compile_native_types());
+        let bindings = signature.try_bind_args(&names(&[None, Some("step"), Some("stop")])).unwrap();
+        assert_eq!(bindings.params, vec![Some(0), Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn missing_default_is_left_unbound() {
+        let signature = range_signature(&compile_native_types());
+        let bindings = signature.try_bind_args(&names(&[None, None])).unwrap();
+        assert_eq!(bindings.params, vec![Some(0), Some(1), None]);
+        assert!(bindings.variadic.is_empty());
+    }
+
+    #[test]
+    fn positional_after_named_is_rejected() {
+        let signature = range_signature(&compile_native_types());
+        let error = signature.try_bind_args(&names(&[Some("start"), None])).err();
+        assert_eq!(error, Some(BindError::PositionalAfterNamed));
+    }
+
+    #[test]
+    fn duplicate_argument_is_rejected() {
+        let signature = range_signature(&compile_native_types());
+        let error = signature.try_bind_args(&names(&[None, None, Some("start")])).err();
+        assert_eq!(error, Some(BindError::DuplicateArgument(0)));
+    }
+
+    #[test]
+    fn unknown_argument_is_rejected() {
+        let signature = range_signature(&compile_native_types());
+        let error = signature.try_bind_args(&names(&[None, None, Some("end")])).err();
+        assert_eq!(error, Some(BindError::UnknownArgument(Rc::new("end".to_string()))));
+    }
+
+    #[test]
+    fn missing_required_argument_is_rejected() {
+        let signature = range_signature(&compile_native_types());
+        let error = signature.try_bind_args(&names(&[None, Some("step")])).err();
+        assert_eq!(error, Some(BindError::MissingArgument(1)));
+
+        let error = signature.bind_args(&names(&[Some("stop")]), 7).err();
+        assert!(matches!(error, Some(MissingArgument(7, _, param)) if param == "start"));
+    }
+
+    #[test]
+    fn too_many_arguments_are_rejected() {
+        let signature = range_signature(&compile_native_types());
+        let error = signature.bind_args(&names(&[None, None, None, None]), 3).err();
+        assert!(matches!(error, Some(ArgumentCountError(3, _, 3, 4))));
+    }
+
+    #[test]
+    fn variadic_may_follow_defaulted_parameter() {
+        let types = compile_native_types();
+        let signature = with_lines("void log(str fmt = \"a\", i64... args)", |lines| {
+            Function::from(&lines[0], &types).unwrap().signature()
+        });
+        assert_eq!(signature.required_count, 0);
+
+        let bindings = signature.bind_args(&names(&[Some("fmt")]), 1).unwrap();
+        assert_eq!(bindings.params, vec![Some(0)]);
+        assert!(bindings.variadic.is_empty());
+
+        let bindings = signature.bind_args(&names(&[None, None, None]), 1).unwrap();
+        assert_eq!(bindings.params, vec![Some(0)]);
+        assert_eq!(bindings.variadic, vec![1, 2]);
+        let (text, number) = (var(&types, STR_TYPE), var(&types, I64_TYPE));
+        assert!(signature.check_arg_types(&bindings, &[&text, &number, &number], 1).is_ok());
+    }
+
+    #[test]
+    fn argument_types_are_checked() {
+        let types = compile_native_types();
+        let signature = range_signature(&types);
+        let (number, text) = (var(&types, I64_TYPE), var(&types, STR_TYPE));
+        let arg_names = names(&[None, None]);
+
+        assert!(signature.accepts(&arg_names, &[&number, &number]).is_some());
+        assert!(signature.accepts(&arg_names, &[&number, &text]).is_none());
+
+        let bindings = signature.bind_args(&arg_names, 2).unwrap();
+        let error = signature.check_arg_types(&bindings, &[&number, &text], 2).err();
+        assert!(matches!(error, Some(ArgumentTypeError(2, _, param, _, _)) if param == "stop"));
+    }
+}
@@ -9,7 +9,6 @@ pub struct Line<'a> {
     pub start: usize,
     pub end: usize,
     pub indent: usize,
-    pub file_name: &'a String,
     pub tokens: &'a [Rc<String>],
 }
 
@@ -22,7 +21,7 @@ impl<'a> Line<'a> {
         self.tokens.len()
     }
 
-    fn set_indent_level(&mut self, tokens: &Vec<Rc<String>>) -> Result<(), CompilerError> {
+    fn set_indent_level(&mut self, tokens: &[Rc<String>]) -> Result<(), CompilerError> {
         const TAB_WIDTH: usize = 4;
 
         let mut count = 0;
@@ -57,14 +56,13 @@ pub struct LineIterator<'a> {
 }
 
 impl<'a> LineIterator<'a> {
-    pub fn new(filename: &'a String, tokens: &'a Vec<Rc<String>>) -> Self {
+    pub fn new(tokens: &'a Vec<Rc<String>>) -> Self {
         LineIterator {
             curr_line: Line {
                 line_num: 0,
                 start: 0,
                 end: 0,
                 indent: 0,
-                file_name: filename,
                 tokens,
             },
             tokens,
@@ -81,13 +79,13 @@ impl<'a> Iterator for LineIterator<'a> {
         line.start = line.end;
 
         line.set_indent_level(self.tokens)
-            .unwrap_or_else(|e| raise_compiler_error(IndentError(line.line_num)));
+            .unwrap_or_else(|e| raise_compiler_error(e));
 
         line.start += 1;
         line.end += 1;
         while line.end < self.tokens.len() {
             let token = &self.tokens[line.end];
-            if token.chars().next().unwrap() == '\n' {
+            if token.starts_with('\n') {
                 line.tokens = &self.tokens[line.start..line.end];
                 return Some(*line);
             }
@@ -97,3 +95,13 @@ impl<'a> Iterator for LineIterator<'a> {
         None
     }
 }
+
+// Non-empty lines of `code`, which borrow the tokens and so only live for the call to `f`
+#[cfg(test)]
+pub fn with_lines<R>(code: &str, f: impl FnOnce(Vec<Line>) -> R) -> R {
+    let tokens = crate::tokenizer::tokenize(code);
+    let lines = LineIterator::new(&tokens)
+        .filter(|line| line.start < line.end)
+        .collect();
+    f(lines)
+}
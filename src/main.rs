@@ -1,4 +1,5 @@
 
+
 mod tokenizer;
 // mod ast;
 mod line;
@@ -7,7 +8,10 @@ mod ast_node;
 mod data_type;
 mod ast;
 mod function_node;
-// mod expression;
+mod expression;
+mod operator_signature;
+mod conversion;
+mod pattern;
 
 fn main() {
     const MIN_ARG_COUNT: usize = 2;
@@ -19,5 +23,5 @@ fn main() {
         panic!("{}: ERROR: No input files", args[0]);
     }
 
-    // generate_ast(&args[1], &mut compile_native_types());
+    ast::generate_ast(&args[1]);
 }
@@ -0,0 +1,136 @@
+use phf::phf_map;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::data_type::{Type, BOOL_TYPE, CHAR_TYPE, STR_TYPE, VOID_TYPE};
+
+#[derive(Copy, Clone)]
+enum OperandClass {
+    Integer,
+    Numeric,
+    Signed,
+    Char,
+    Bool,
+    Str,
+    Any,
+}
+
+impl OperandClass {
+    fn contains(self, data_type: &Type) -> bool {
+        match self {
+            OperandClass::Integer => data_type.is_integer(),
+            OperandClass::Numeric => data_type.is_numeric(),
+            OperandClass::Signed => data_type.is_signed_integer() || data_type.is_float(),
+            OperandClass::Char => data_type.name() == CHAR_TYPE,
+            OperandClass::Bool => data_type.name() == BOOL_TYPE,
+            OperandClass::Str => data_type.name() == STR_TYPE,
+            OperandClass::Any => data_type.name() != VOID_TYPE,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum ResultType {
+    Operand,
+    Bool,
+}
+
+struct BinarySignature {
+    left: OperandClass,
+    right: OperandClass,
+    same_type: bool,
+    result: ResultType,
+}
+
+struct UnarySignature {
+    operand: OperandClass,
+    result: ResultType,
+}
+
+const fn same_operands(operands: OperandClass, result: ResultType) -> BinarySignature {
+    BinarySignature { left: operands, right: operands, same_type: true, result }
+}
+
+const fn mixed_operands(left: OperandClass, right: OperandClass, result: ResultType) -> BinarySignature {
+    BinarySignature { left, right, same_type: false, result }
+}
+
+const ARITHMETIC: &[BinarySignature] = &[same_operands(OperandClass::Numeric, ResultType::Operand)];
+const INTEGER_ARITHMETIC: &[BinarySignature] = &[same_operands(OperandClass::Integer, ResultType::Operand)];
+const ORDERING: &[BinarySignature] = &[
+    same_operands(OperandClass::Numeric, ResultType::Bool),
+    same_operands(OperandClass::Char, ResultType::Bool),
+];
+const EQUALITY: &[BinarySignature] = &[same_operands(OperandClass::Any, ResultType::Bool)];
+const LOGICAL: &[BinarySignature] = &[same_operands(OperandClass::Bool, ResultType::Bool)];
+const SHIFT: &[BinarySignature] = &[
+    mixed_operands(OperandClass::Integer, OperandClass::Integer, ResultType::Operand)
+];
+
+// Accepted operand types of every binary operator, tried in order. The result type is
+// either the left operand's type or bool.
+static BINARY_OPERATOR_SIGNATURES: phf::Map<&'static str, &'static [BinarySignature]> = phf_map! {
+    "+" => &[
+        same_operands(OperandClass::Numeric, ResultType::Operand),
+        same_operands(OperandClass::Str, ResultType::Operand),
+    ],
+    "-" => ARITHMETIC,
+    "*" => ARITHMETIC,
+    "/" => ARITHMETIC,
+    "%" => INTEGER_ARITHMETIC,
+    "&" => INTEGER_ARITHMETIC,
+    "|" => INTEGER_ARITHMETIC,
+    "^" => INTEGER_ARITHMETIC,
+    "<<" => SHIFT,
+    ">>" => SHIFT,
+    "==" => EQUALITY,
+    "!=" => EQUALITY,
+    "<" => ORDERING,
+    "<=" => ORDERING,
+    ">" => ORDERING,
+    ">=" => ORDERING,
+    "&&" => LOGICAL,
+    "||" => LOGICAL,
+};
+
+static UNARY_OPERATOR_SIGNATURES: phf::Map<&'static str, UnarySignature> = phf_map! {
+    "-" => UnarySignature { operand: OperandClass::Signed, result: ResultType::Operand },
+    "~" => UnarySignature { operand: OperandClass::Integer, result: ResultType::Operand },
+    "!" => UnarySignature { operand: OperandClass::Bool, result: ResultType::Operand },
+};
+
+fn resolve_result_type(result: ResultType,
+                       operand: &Rc<Type>,
+                       types: &HashMap<String, Rc<Type>>
+) -> Rc<Type> {
+    match result {
+        ResultType::Operand => operand.clone(),
+        ResultType::Bool => types[BOOL_TYPE].clone(),
+    }
+}
+
+pub fn binary_result_type(operator: &str,
+                          left: &Rc<Type>,
+                          right: &Rc<Type>,
+                          types: &HashMap<String, Rc<Type>>
+) -> Option<Rc<Type>> {
+    BINARY_OPERATOR_SIGNATURES.get(operator)?
+        .iter()
+        .find(|signature| {
+            signature.left.contains(left)
+                && signature.right.contains(right)
+                && (!signature.same_type || left == right)
+        })
+        .map(|signature| resolve_result_type(signature.result, left, types))
+}
+
+pub fn unary_result_type(operator: &str,
+                         operand: &Rc<Type>,
+                         types: &HashMap<String, Rc<Type>>
+) -> Option<Rc<Type>> {
+    let signature = UNARY_OPERATOR_SIGNATURES.get(operator)?;
+    if signature.operand.contains(operand) {
+        Some(resolve_result_type(signature.result, operand, types))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,652 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::ast_node::{ASTNode, Mutability, VarNode};
+use crate::compiler_error::CompilerError;
+use crate::compiler_error::CompilerError::{InconsistentBinding, InvalidPattern, LiteralOutOfRange, NotMatchable, PatternFieldCount, PatternTypeError, SymbolAlreadyDefined, UnexpectedToken, UnknownVariant};
+use crate::data_type::{char_value, type_literal, Type, Variant, BOOL_TYPE, CHAR_TYPE, I16_TYPE, I32_TYPE, I64_TYPE, I8_TYPE, STR_TYPE, U16_TYPE, U32_TYPE, U64_TYPE, U8_TYPE};
+use crate::function_node::assert_valid_symbol;
+use crate::line::Line;
+
+const WILDCARD_TOKEN: &str = "_";
+const ALTERNATIVE_TOKEN: &str = "|";
+const NEGATIVE_TOKEN: &str = "-";
+const RANGE_TOKEN: &str = "..";
+const INCLUSIVE_RANGE_TOKEN: &str = "..=";
+const VARIANT_TOKEN: &str = ".";
+const PAREN_OPEN_TOKEN: &str = "(";
+const PAREN_CLOSE_TOKEN: &str = ")";
+const FIELD_DELIMITER: &str = ",";
+
+// Integer, char and bool values are matched by inclusive ranges, a literal being a range of one value
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Wildcard,
+    Binding(VarNode),
+    Range(i128, i128),
+    Str(Rc<String>),
+    Variant(usize, Vec<Pattern>),
+    Or(Vec<Pattern>),
+}
+
+const WILDCARD: &Pattern = &Pattern::Wildcard;
+
+impl Pattern {
+    // Every alternative binds the same variables, so the first one stands for all of them
+    pub fn bindings(&self) -> Vec<VarNode> {
+        match self {
+            Pattern::Binding(var) => vec![var.clone()],
+            Pattern::Variant(_, fields) => fields.iter().flat_map(Pattern::bindings).collect(),
+            Pattern::Or(alternatives) => alternatives[0].bindings(),
+            Pattern::Wildcard | Pattern::Range(..) | Pattern::Str(_) => Vec::new(),
+        }
+    }
+
+    pub fn describe(&self, data_type: &Type) -> String {
+        match self {
+            Pattern::Wildcard => WILDCARD_TOKEN.to_string(),
+            Pattern::Binding(var) => var.name().to_string(),
+            Pattern::Range(start, end) => Constructor::Range(*start, *end).describe(data_type, &[]),
+            Pattern::Str(value) => value.to_string(),
+            Pattern::Variant(discriminant, fields) => {
+                let variant = &data_type.variants().unwrap()[*discriminant];
+                let fields: Vec<String> = fields.iter()
+                    .zip(&variant.fields)
+                    .map(|(pattern, field)| pattern.describe(&field.data_type))
+                    .collect();
+                Constructor::Variant(variant).describe(data_type, &fields)
+            }
+            Pattern::Or(alternatives) => {
+                let alternatives: Vec<String> = alternatives.iter().map(|pattern| pattern.describe(data_type)).collect();
+                alternatives.join(&format!(" {ALTERNATIVE_TOKEN} "))
+            }
+        }
+    }
+}
+
+pub fn is_matchable(data_type: &Type) -> bool {
+    value_ranges(data_type).is_some() || data_type.variants().is_some() || data_type.name() == STR_TYPE
+}
+
+// All values of a type that range patterns can match
+fn value_ranges(data_type: &Type) -> Option<Vec<(i128, i128)>> {
+    let range = match data_type.name() {
+        I8_TYPE => (i8::MIN.into(), i8::MAX.into()),
+        I16_TYPE => (i16::MIN.into(), i16::MAX.into()),
+        I32_TYPE => (i32::MIN.into(), i32::MAX.into()),
+        I64_TYPE => (i64::MIN.into(), i64::MAX.into()),
+        U8_TYPE => (0, u8::MAX.into()),
+        U16_TYPE => (0, u16::MAX.into()),
+        U32_TYPE => (0, u32::MAX.into()),
+        U64_TYPE => (0, u64::MAX.into()),
+        BOOL_TYPE => (0, 1),
+        // Surrogate code points are not chars
+        CHAR_TYPE => return Some(vec![(0, 0xD7FF), (0xE000, 0x10FFFF)]),
+        _ => return None,
+    };
+
+    Some(vec![range])
+}
+
+fn literal_value(data_type: &Type, literal: &str) -> Option<i128> {
+    match data_type.name() {
+        BOOL_TYPE => Some((literal == "true").into()),
+        CHAR_TYPE => char_value(literal).map(|char| u32::from(char).into()),
+        _ if data_type.is_integer() => literal.parse().ok(),
+        _ => None,
+    }
+}
+
+fn describe_value(data_type: &Type, value: i128) -> String {
+    match data_type.name() {
+        BOOL_TYPE => (value != 0).to_string(),
+        CHAR_TYPE => u32::try_from(value).ok()
+            .and_then(char::from_u32)
+            .map_or(value.to_string(), |char| format!("{char:?}")),
+        _ => value.to_string(),
+    }
+}
+
+struct PatternParser<'a> {
+    line: Line<'a>,
+    start: usize,
+    index: usize,
+    end: usize,
+    types: &'a HashMap<String, Rc<Type>>,
+}
+
+// `_`, a binding, a literal, a literal range such as `1..=9`, or `Enum.Variant(pattern, ...)`,
+// optionally followed by alternatives as in `1 | 2`
+pub fn parse_pattern(line: &Line,
+                     start: usize,
+                     end: usize,
+                     types: &HashMap<String, Rc<Type>>,
+                     data_type: &Rc<Type>
+) -> Result<Pattern, CompilerError> {
+    let mut parser = PatternParser { line: *line, start, index: start, end, types };
+    let pattern = parser.parse_alternatives(data_type)?;
+    if parser.index < end {
+        return Err(parser.invalid());
+    }
+
+    Ok(pattern)
+}
+
+impl<'a> PatternParser<'a> {
+    fn invalid(&self) -> CompilerError {
+        InvalidPattern(self.line.line_num, self.text(self.start, self.end))
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.line.tokens[start..end].iter()
+            .map(|token| match token.as_str() {
+                FIELD_DELIMITER => format!("{FIELD_DELIMITER} "),
+                ALTERNATIVE_TOKEN => format!(" {ALTERNATIVE_TOKEN} "),
+                token => token.to_string(),
+            })
+            .collect()
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        (self.index < self.end).then(|| self.line.get_token(self.index).as_str())
+    }
+
+    fn next_token(&mut self) -> Result<&'a str, CompilerError> {
+        let token = self.peek().ok_or(self.invalid())?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), CompilerError> {
+        match self.peek() {
+            Some(token) if token == expected => {
+                self.index += 1;
+                Ok(())
+            }
+            _ => Err(UnexpectedToken(self.line.line_num, expected.to_string())),
+        }
+    }
+
+    // A literal with its sign, which is a separate token
+    fn next_literal(&mut self) -> Result<String, CompilerError> {
+        match self.next_token()? {
+            NEGATIVE_TOKEN => Ok(format!("{NEGATIVE_TOKEN}{}", self.next_token()?)),
+            token => Ok(token.to_string()),
+        }
+    }
+
+    fn parse_alternatives(&mut self, data_type: &Rc<Type>) -> Result<Pattern, CompilerError> {
+        let first = self.parse_alternative(data_type)?;
+        let bindings = self.unique_bindings(&first)?;
+        if self.peek() != Some(ALTERNATIVE_TOKEN) {
+            return Ok(first);
+        }
+
+        let mut alternatives = vec![first];
+        while self.peek() == Some(ALTERNATIVE_TOKEN) {
+            self.index += 1;
+            let alternative = self.parse_alternative(data_type)?;
+            let alternative_bindings = self.unique_bindings(&alternative)?;
+
+            let is_bound_in = |var: &VarNode, bindings: &[VarNode]| {
+                bindings.iter().any(|other| other.name() == var.name() && other.get_type() == var.get_type())
+            };
+            let inconsistent = bindings.iter()
+                .chain(&alternative_bindings)
+                .find(|var| !is_bound_in(var, &bindings) || !is_bound_in(var, &alternative_bindings));
+            if let Some(var) = inconsistent {
+                return Err(InconsistentBinding(self.line.line_num, var.name().to_string()));
+            }
+            alternatives.push(alternative);
+        }
+
+        Ok(Pattern::Or(alternatives))
+    }
+
+    fn unique_bindings(&self, pattern: &Pattern) -> Result<Vec<VarNode>, CompilerError> {
+        let bindings = pattern.bindings();
+        for (i, var) in bindings.iter().enumerate() {
+            if bindings[..i].iter().any(|other| other.name() == var.name()) {
+                return Err(SymbolAlreadyDefined(self.line.line_num, var.name().to_string()));
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    fn parse_alternative(&mut self, data_type: &Rc<Type>) -> Result<Pattern, CompilerError> {
+        let start = self.index;
+        let token = self.next_token()?;
+        if token == WILDCARD_TOKEN {
+            return Ok(Pattern::Wildcard);
+        }
+        let types = self.types;
+        if let Some(enum_type) = types.get(token) {
+            return self.parse_variant(start, enum_type, data_type);
+        }
+
+        self.index = start;
+        let literal = self.next_literal()?;
+        let Some(value) = self.literal(&literal, data_type)? else {
+            if token == NEGATIVE_TOKEN {
+                return Err(self.invalid());
+            }
+            let name = self.line[start].clone();
+            assert_valid_symbol(name.clone(), &self.line);
+            return Ok(Pattern::Binding(VarNode::new(data_type.clone(), name, Mutability::Final)));
+        };
+        if data_type.name() == STR_TYPE {
+            return Ok(Pattern::Str(Rc::new(value)));
+        }
+
+        let not_matchable = NotMatchable(self.line.line_num, data_type.to_string());
+        let start_value = literal_value(data_type, &value).ok_or(not_matchable)?;
+        let inclusive = match self.peek() {
+            Some(RANGE_TOKEN) => false,
+            Some(INCLUSIVE_RANGE_TOKEN) => true,
+            _ => return Ok(Pattern::Range(start_value, start_value)),
+        };
+        self.index += 1;
+
+        let literal = self.next_literal()?;
+        let end_value = self.literal(&literal, data_type)?
+            .and_then(|value| literal_value(data_type, &value))
+            .filter(|_| data_type.name() != BOOL_TYPE)
+            .ok_or(self.invalid())?;
+        let end_value = if inclusive { end_value } else { end_value - 1 };
+        if end_value < start_value {
+            return Err(InvalidPattern(self.line.line_num, self.text(start, self.index)));
+        }
+
+        Ok(Pattern::Range(start_value, end_value))
+    }
+
+    // Value of a literal typed to match `data_type`, None if the token is not a literal
+    fn literal(&self, literal: &str, data_type: &Rc<Type>) -> Result<Option<String>, CompilerError> {
+        let line_num = self.line.line_num;
+        let Some(typed) = type_literal(self.types, literal, Some(data_type)) else {
+            return Ok(None);
+        };
+
+        if !typed.data_type.is_valid_literal(&typed.value) {
+            return Err(LiteralOutOfRange(line_num, literal.to_string(), typed.data_type.to_string()));
+        }
+        if typed.data_type != *data_type {
+            return match !typed.suffixed && typed.data_type.same_kind(data_type) {
+                true => Err(LiteralOutOfRange(line_num, literal.to_string(), data_type.to_string())),
+                false => Err(PatternTypeError(line_num, literal.to_string(), data_type.to_string())),
+            };
+        }
+
+        Ok(Some(typed.value))
+    }
+
+    fn parse_variant(&mut self,
+                     start: usize,
+                     enum_type: &'a Rc<Type>,
+                     data_type: &Rc<Type>
+    ) -> Result<Pattern, CompilerError> {
+        let line_num = self.line.line_num;
+        self.expect(VARIANT_TOKEN)?;
+        let variant_name = self.next_token()?;
+        let text = self.text(start, self.index);
+
+        if enum_type != data_type {
+            return Err(PatternTypeError(line_num, text, data_type.to_string()));
+        }
+        let variant = enum_type.variant(variant_name)
+            .ok_or(UnknownVariant(line_num, enum_type.to_string(), variant_name.to_string()))?;
+
+        let field_count = self.field_count();
+        if field_count != variant.fields.len() {
+            return Err(PatternFieldCount(line_num, text, variant.fields.len(), field_count));
+        }
+
+        let mut fields = Vec::with_capacity(field_count);
+        if field_count > 0 {
+            self.expect(PAREN_OPEN_TOKEN)?;
+            for (i, field) in variant.fields.iter().enumerate() {
+                if i > 0 {
+                    self.expect(FIELD_DELIMITER)?;
+                }
+                fields.push(self.parse_alternatives(&field.data_type)?);
+            }
+            self.expect(PAREN_CLOSE_TOKEN)?;
+        }
+
+        Ok(Pattern::Variant(variant.discriminant, fields))
+    }
+
+    // Number of field patterns in the parentheses that follow, without consuming them
+    fn field_count(&self) -> usize {
+        if self.peek() != Some(PAREN_OPEN_TOKEN) {
+            return 0;
+        }
+
+        let mut depth = 1;
+        let mut delimiters = 0;
+        let mut is_empty = true;
+        for token in &self.line.tokens[self.index + 1..self.end] {
+            match token.as_str() {
+                PAREN_OPEN_TOKEN => depth += 1,
+                PAREN_CLOSE_TOKEN if depth == 1 => break,
+                PAREN_CLOSE_TOKEN => depth -= 1,
+                FIELD_DELIMITER if depth == 1 => delimiters += 1,
+                _ => {}
+            }
+            is_empty = false;
+        }
+
+        if is_empty { 0 } else { delimiters + 1 }
+    }
+}
+
+type Row<'p> = Vec<&'p Pattern>;
+
+// A set of values that every row either matches entirely or not at all
+enum Constructor<'t> {
+    Variant(&'t Variant),
+    Range(i128, i128),
+    Str(Rc<String>),
+}
+
+impl Constructor<'_> {
+    fn field_types(&self) -> Vec<Rc<Type>> {
+        match self {
+            Constructor::Variant(variant) => variant.fields.iter().map(|field| field.data_type.clone()).collect(),
+            Constructor::Range(..) | Constructor::Str(_) => Vec::new(),
+        }
+    }
+
+    // Field patterns and the rest of a row whose head matches the constructor, None if it does not
+    fn specialize<'p>(&self, row: &[&'p Pattern]) -> Option<Row<'p>> {
+        let (head, tail) = row.split_first()?;
+        let fields = match (head, self) {
+            (Pattern::Wildcard | Pattern::Binding(_), _) => vec![WILDCARD; self.field_types().len()],
+            (Pattern::Variant(discriminant, fields), Constructor::Variant(variant)) if *discriminant == variant.discriminant => {
+                fields.iter().collect()
+            }
+            (Pattern::Range(start, end), Constructor::Range(low, high)) if start <= low && high <= end => Vec::new(),
+            (Pattern::Str(value), Constructor::Str(other)) if value == other => Vec::new(),
+            _ => return None,
+        };
+
+        Some([fields.as_slice(), tail].concat())
+    }
+
+    fn describe(&self, data_type: &Type, fields: &[String]) -> String {
+        match self {
+            Constructor::Variant(variant) if fields.is_empty() => format!("{data_type}{VARIANT_TOKEN}{}", variant.name),
+            Constructor::Variant(variant) => format!("{data_type}{VARIANT_TOKEN}{}({})", variant.name, fields.join(", ")),
+            Constructor::Range(low, high) if low == high => describe_value(data_type, *low),
+            Constructor::Range(low, high) => {
+                format!("{}{INCLUSIVE_RANGE_TOKEN}{}", describe_value(data_type, *low), describe_value(data_type, *high))
+            }
+            Constructor::Str(value) => value.to_string(),
+        }
+    }
+}
+
+// Cuts `ranges` at the bounds of the range patterns heading the rows, so each piece is
+// either inside or outside every one of them
+fn split_ranges<'t>(ranges: &[(i128, i128)], matrix: &[Row]) -> Vec<Constructor<'t>> {
+    let mut cuts: Vec<i128> = matrix.iter()
+        .filter_map(|row| match row[0] {
+            Pattern::Range(start, end) => Some([*start, end + 1]),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let mut pieces = Vec::new();
+    for &(low, high) in ranges {
+        let mut piece_start = low;
+        for &cut in cuts.iter().filter(|&&cut| low < cut && cut <= high) {
+            pieces.push(Constructor::Range(piece_start, cut - 1));
+            piece_start = cut;
+        }
+        pieces.push(Constructor::Range(piece_start, high));
+    }
+
+    pieces
+}
+
+// Every constructor of a type, None for str and the types only wildcards can match
+fn all_constructors<'t>(data_type: &'t Type, matrix: &[Row]) -> Option<Vec<Constructor<'t>>> {
+    if let Some(variants) = data_type.variants() {
+        return Some(variants.iter().map(Constructor::Variant).collect());
+    }
+
+    value_ranges(data_type).map(|ranges| split_ranges(&ranges, matrix))
+}
+
+fn is_wildcard(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Wildcard | Pattern::Binding(_))
+}
+
+// Rows headed by alternatives become one row per alternative
+fn expand_alternatives<'p>(matrix: &[Row<'p>]) -> Vec<Row<'p>> {
+    let mut expanded = Vec::with_capacity(matrix.len());
+    for row in matrix {
+        match row[0] {
+            Pattern::Or(alternatives) => {
+                let rows: Vec<Row> = alternatives.iter().map(|alternative| [&[alternative], &row[1..]].concat()).collect();
+                expanded.extend(expand_alternatives(&rows));
+            }
+            _ => expanded.push(row.clone()),
+        }
+    }
+
+    expanded
+}
+
+// Describes, one column at a time, a value that `row` matches and no row of `matrix` does,
+// or None if there is no such value
+fn uncovered<'p>(matrix: &[Row<'p>], row: &[&'p Pattern], types: &[Rc<Type>]) -> Option<Vec<String>> {
+    let Some((&head, tail)) = row.split_first() else {
+        return matrix.is_empty().then(Vec::new);
+    };
+    let matrix = expand_alternatives(matrix);
+    let data_type = &types[0];
+
+    let constructors = match head {
+        Pattern::Or(alternatives) => {
+            return alternatives.iter()
+                .find_map(|alternative| uncovered(&matrix, &[&[alternative], tail].concat(), types));
+        }
+        Pattern::Wildcard | Pattern::Binding(_) => match all_constructors(data_type, &matrix) {
+            Some(constructors) if !matrix.iter().all(|row| is_wildcard(row[0])) => constructors,
+            _ => {
+                // Some value is left out by every pattern other than a wildcard, and any value
+                // will do if there are none
+                let rest: Vec<Row> = matrix.iter()
+                    .filter(|row| is_wildcard(row[0]))
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                let mut witness = uncovered(&rest, tail, &types[1..])?;
+                witness.insert(0, WILDCARD_TOKEN.to_string());
+                return Some(witness);
+            }
+        },
+        Pattern::Range(start, end) => split_ranges(&[(*start, *end)], &matrix),
+        Pattern::Str(value) => vec![Constructor::Str(value.clone())],
+        Pattern::Variant(discriminant, _) => vec![Constructor::Variant(&data_type.variants()?[*discriminant])],
+    };
+
+    constructors.iter().find_map(|constructor| {
+        let specialized: Vec<Row> = matrix.iter().filter_map(|row| constructor.specialize(row)).collect();
+        let field_types = constructor.field_types();
+        let field_count = field_types.len();
+        let types = [field_types.as_slice(), &types[1..]].concat();
+
+        let mut witness = uncovered(&specialized, &constructor.specialize(row)?, &types)?;
+        let fields: Vec<String> = witness.drain(..field_count).collect();
+        witness.insert(0, constructor.describe(data_type, &fields));
+        Some(witness)
+    })
+}
+
+// Patterns of the unguarded arms of a match so far. Guarded arms may not match, so they
+// cover nothing.
+pub struct Coverage {
+    data_type: Rc<Type>,
+    patterns: Vec<Pattern>,
+}
+
+impl Coverage {
+    pub fn new(data_type: Rc<Type>) -> Self {
+        Self { data_type, patterns: Vec::new() }
+    }
+
+    pub fn add(&mut self, pattern: Pattern) {
+        self.patterns.push(pattern);
+    }
+
+    fn uncovered(&self, pattern: &Pattern, earlier: &[&Pattern]) -> Option<Vec<String>> {
+        let matrix: Vec<Row> = self.patterns.iter()
+            .chain(earlier.iter().copied())
+            .map(|pattern| vec![pattern])
+            .collect();
+        uncovered(&matrix, &[pattern], std::slice::from_ref(&self.data_type))
+    }
+
+    // First alternative of `pattern` that only matches values matched before it
+    pub fn unreachable(&self, pattern: &Pattern) -> Option<String> {
+        let alternatives: Vec<&Pattern> = match pattern {
+            Pattern::Or(alternatives) => alternatives.iter().collect(),
+            _ => vec![pattern],
+        };
+
+        (0..alternatives.len())
+            .find(|&i| self.uncovered(alternatives[i], &alternatives[..i]).is_none())
+            .map(|i| alternatives[i].describe(&self.data_type))
+    }
+
+    // A value no arm matches
+    pub fn missing(&self) -> Option<String> {
+        self.uncovered(WILDCARD, &[]).map(|mut witness| witness.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_type::{compile_native_types, F64_TYPE};
+    use crate::line::with_lines;
+
+    fn declare(types: &mut HashMap<String, Rc<Type>>, name: &str, variants: &[(&str, &[&str])]) {
+        let variants = variants.iter()
+            .map(|(variant, fields)| {
+                let fields = fields.iter()
+                    .enumerate()
+                    .map(|(i, field_type)| (Rc::new(format!("f{i}")), types[*field_type].clone()))
+                    .collect();
+                (Rc::new(variant.to_string()), fields)
+            })
+            .collect();
+        types.insert(name.to_string(), Rc::new(Type::enumeration(name.to_string(), variants)));
+    }
+
+    fn enum_types() -> HashMap<String, Rc<Type>> {
+        let mut types = compile_native_types();
+        declare(&mut types, "Shape", &[("Circle", &[F64_TYPE]), ("Rect", &[I64_TYPE, I64_TYPE])]);
+        declare(&mut types, "Opt", &[("None", &[]), ("Some", &["Shape"])]);
+        types
+    }
+
+    fn pattern(types: &HashMap<String, Rc<Type>>, data_type: &str, text: &str) -> Pattern {
+        with_lines(text, |lines| parse_pattern(&lines[0], 0, lines[0].len(), types, &types[data_type]).unwrap())
+    }
+
+    // Adds the arms in order, returning the first unreachable one and a value none of them match
+    fn check(types: &HashMap<String, Rc<Type>>, data_type: &str, arms: &[&str]) -> (Option<String>, Option<String>) {
+        let mut coverage = Coverage::new(types[data_type].clone());
+        let mut unreachable = None;
+        for arm in arms {
+            let pattern = pattern(types, data_type, arm);
+            unreachable = unreachable.or(coverage.unreachable(&pattern));
+            coverage.add(pattern);
+        }
+
+        (unreachable, coverage.missing())
+    }
+
+    #[test]
+    fn ranges_split_at_type_bounds() {
+        let types = compile_native_types();
+        assert_eq!(check(&types, U8_TYPE, &["0..=127", "128..=255"]), (None, None));
+        assert_eq!(check(&types, I8_TYPE, &["-127..=-1", "0..=127"]), (None, Some("-128".to_string())));
+        assert_eq!(check(&types, I8_TYPE, &["-128..0", "0..127"]), (None, Some("127".to_string())));
+        assert_eq!(check(&types, I64_TYPE, &["-9223372036854775808..=9223372036854775807"]), (None, None));
+    }
+
+    #[test]
+    fn overlapping_ranges_make_later_arm_unreachable() {
+        let types = compile_native_types();
+        let (unreachable, _) = check(&types, I32_TYPE, &["0..=10", "3..5"]);
+        assert_eq!(unreachable.as_deref(), Some("3..=4"));
+
+        let (unreachable, _) = check(&types, I32_TYPE, &["0..=5", "6..=10", "2..=8"]);
+        assert_eq!(unreachable.as_deref(), Some("2..=8"));
+
+        let (unreachable, _) = check(&types, I32_TYPE, &["0..=5", "3..=8"]);
+        assert_eq!(unreachable, None);
+    }
+
+    #[test]
+    fn guarded_arms_do_not_cover() {
+        let types = compile_native_types();
+        let mut coverage = Coverage::new(types[BOOL_TYPE].clone());
+        coverage.add(pattern(&types, BOOL_TYPE, "true"));
+        // A guarded `false` arm is checked for reachability but never added
+        let guarded = pattern(&types, BOOL_TYPE, "false");
+        assert_eq!(coverage.unreachable(&guarded), None);
+        assert_eq!(coverage.missing().as_deref(), Some("false"));
+    }
+
+    #[test]
+    fn or_patterns() {
+        let types = compile_native_types();
+        assert_eq!(check(&types, U8_TYPE, &["0 | 1", "2..=255"]), (None, None));
+        assert_eq!(check(&types, U8_TYPE, &["0 | 2", "3..=255"]), (None, Some("1".to_string())));
+
+        let (unreachable, _) = check(&types, U8_TYPE, &["0..=9", "5 | 10"]);
+        assert_eq!(unreachable.as_deref(), Some("5"));
+        let (unreachable, _) = check(&types, U8_TYPE, &["1 | 1"]);
+        assert_eq!(unreachable.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn nested_variant_witness() {
+        let types = enum_types();
+        let arms = ["Opt.None", "Opt.Some(Shape.Circle(_))", "Opt.Some(Shape.Rect(0, _))"];
+        let (unreachable, missing) = check(&types, "Opt", &arms);
+        assert_eq!(unreachable, None);
+        assert_eq!(missing.as_deref(), Some("Opt.Some(Shape.Rect(-9223372036854775808..=-1, _))"));
+
+        assert_eq!(check(&types, "Opt", &["Opt.None"]), (None, Some("Opt.Some(_)".to_string())));
+        let arms = ["Opt.Some(Shape.Circle(_))", "Opt.Some(Shape.Rect(_, _))", "Opt.None"];
+        assert_eq!(check(&types, "Opt", &arms), (None, None));
+
+        let (unreachable, _) = check(&types, "Opt", &["Opt.Some(_)", "Opt.Some(Shape.Circle(_))"]);
+        assert_eq!(unreachable.as_deref(), Some("Opt.Some(Shape.Circle(_))"));
+    }
+
+    #[test]
+    fn bool_char_and_str_scrutinees() {
+        let types = compile_native_types();
+        assert_eq!(check(&types, BOOL_TYPE, &["true", "false"]), (None, None));
+        assert_eq!(check(&types, BOOL_TYPE, &["false"]), (None, Some("true".to_string())));
+
+        let (_, missing) = check(&types, CHAR_TYPE, &["'a'..='z'"]);
+        assert_eq!(missing.as_deref(), Some("'\\0'..='`'"));
+        let (unreachable, _) = check(&types, CHAR_TYPE, &["'a'..='z'", "'q'"]);
+        assert_eq!(unreachable.as_deref(), Some("'q'"));
+
+        assert_eq!(check(&types, STR_TYPE, &["\"a\"", "\"b\""]), (None, Some("_".to_string())));
+        assert_eq!(check(&types, STR_TYPE, &["\"a\"", "other"]), (None, None));
+        let (unreachable, _) = check(&types, STR_TYPE, &["\"a\"", "\"a\""]);
+        assert_eq!(unreachable.as_deref(), Some("\"a\""));
+    }
+}
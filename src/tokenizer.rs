@@ -12,13 +12,16 @@ fn read_source_file(name: &String) -> io::Result<String> {
 }
 
 pub fn tokenize_file(filename: &String) -> io::Result<Vec<Rc<String>>> {
-    const TOKEN_REGEX: &str = "\n[ \t]*|[-+*/%|&~^()=,]|\\w+|\".*?\"";
-    let regex = Regex::new(TOKEN_REGEX).unwrap();
+    let code = read_source_file(filename)?;
+    Ok(tokenize(&code))
+}
 
-    let code= read_source_file(filename)?;
+pub fn tokenize(code: &str) -> Vec<Rc<String>> {
+    const TOKEN_REGEX: &str = "\n[ \t]*|\\.\\.\\.|\\.\\.=|\\.\\.|->|<<|>>|==|!=|<=|>=|&&|\\|\\||[-+*/%|&~^!<>()=,:.]|\\d+(\\.\\d+)?([eE][-+]?\\d+)?\\w*|\\w+|\".*?\"|'(\\\\.|[^'\\\\])'";
+    let regex = Regex::new(TOKEN_REGEX).unwrap();
 
     let mut tokens = vec![Rc::new("\n".to_string())];
-    regex.captures_iter(&code).for_each(|token| tokens.push(Rc::new(token[0].to_string())));
+    regex.captures_iter(code).for_each(|token| tokens.push(Rc::new(token[0].to_string())));
     tokens.push(Rc::new("\n".to_string()));
-    Ok(tokens)
+    tokens
 }